@@ -23,6 +23,9 @@ pub struct OutputConfig {
     pub scale: Option<String>,
     pub transform: Option<String>,
     pub position: Option<PositionConfig>,
+    pub layout: Option<String>, // 该显示器所有标签的默认布局，覆盖 [layout] mode
+    pub inner_gaps: Option<String>, // 该显示器的间隙，覆盖 [window] 中的设置
+    pub outer_gaps: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -33,6 +36,29 @@ pub struct WaybarConfig {
     pub empty_style: Option<String>,
 }
 
+// [status] 模板：标签可用 {index} {icon} {count}，整行可用 {tags} {layout}，两者都能用 {title} {app_id} {mode}
+#[derive(Deserialize, Debug, Clone)]
+pub struct StatusConfig {
+    pub tag: Option<String>,
+    pub focused_tag: Option<String>,
+    pub occupied_tag: Option<String>,
+    pub empty_tag: Option<String>,
+    pub separator: Option<String>,
+    pub format: Option<String>,
+    pub tooltip: Option<String>,
+}
+
+// [layout] 默认布局：mode = "tree" / "master_stack" / "monocle" / "scroller"
+// master_* 只对 master_stack 生效，column_widths 只对 scroller 生效
+#[derive(Deserialize, Debug, Clone)]
+pub struct LayoutConfig {
+    pub mode: Option<String>,
+    pub master_count: Option<String>,
+    pub master_ratio: Option<String>,
+    pub master_location: Option<String>,
+    pub column_widths: Option<Vec<String>>, // 列宽预设，"50%" 或像素，第一个为新列的默认宽度
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct AnimationsConfig {
     pub enable: Option<String>,
@@ -57,6 +83,9 @@ pub struct BorderParams {
     // 使用 serde(rename) 支持 resize_color 或 resize-color
     #[serde(rename = "resize_color", alias = "resize-color")]
     pub resize_color: Option<String>,
+    // 有预选 (presel) 的窗口使用的边框颜色
+    #[serde(rename = "presel_color", alias = "presel-color")]
+    pub presel_color: Option<String>,
 }
 
 // 定义 active 分组
@@ -75,6 +104,9 @@ pub struct WindowRuleMatch {
     pub height: Option<String>,
     pub floating: Option<String>,
     pub fullscreen: Option<String>,
+    pub sticky: Option<String>, // 悬浮并在所有标签上显示
+    // "true": 终端，从它启动的平铺窗口会顶替它；"false": 从不吞掉终端
+    pub swallow: Option<String>,
 }
 
 // --- 定义 rule 分组 ---
@@ -85,12 +117,37 @@ pub struct WindowRuleConfig {
     pub matches: Option<Vec<WindowRuleMatch>>,
 }
 
+// 按标签覆盖的间隙
+#[derive(Deserialize, Debug, Clone)]
+pub struct GapsConfig {
+    pub inner_gaps: Option<String>,
+    pub outer_gaps: Option<String>,
+}
+
+// [scratchpad.<名字>]：按 appid/title 认领窗口，没有运行时用 cmd 启动
+#[derive(Deserialize, Debug, Clone)]
+pub struct ScratchpadConfig {
+    pub appid: Option<String>,
+    pub title: Option<String>,
+    pub cmd: Option<String>,
+    pub width: Option<String>,    // "80%" 或像素，默认 80%
+    pub height: Option<String>,   // 同上
+    pub position: Option<String>, // center (默认) / top / bottom
+}
+
 // 定义 window 分组
 #[derive(Deserialize, Debug, Clone)]
 pub struct WindowConfig {
     #[serde(alias = "smart-borders", default)]
     pub smart_borders: String,
-    pub gaps: Option<String>,
+    pub gaps: Option<String>,       // 旧写法：内外间隙相同
+    pub inner_gaps: Option<String>, // 相邻窗口之间的间隙
+    pub outer_gaps: Option<String>, // 到屏幕边缘的间隙，"上 右 下 左" (可简写为 1 或 2 个值)
+    pub tag_gaps: Option<HashMap<String, GapsConfig>>, // 按标签覆盖，键为标签序号
+    pub sticky_all_outputs: Option<String>, // 置顶窗口是否也跟随焦点显示器
+    pub mru_commit_delay: Option<String>, // 停止连按 cycle_mru 多久后确认选择 (毫秒)
+    pub split_policy: Option<String>, // tree 布局新窗口的切分策略：longest_side / dwindle / spiral / grid
+    pub tag_split_policies: Option<HashMap<String, String>>, // 按标签覆盖，键为标签序号 ("3" = "grid")
     pub active: Option<ActiveConfig>,
     pub rule: Option<WindowRuleConfig>,
 }
@@ -122,6 +179,16 @@ pub enum KeyBindingEntry {
     Group(HashMap<String, Box<KeyBindingEntry>>),
 }
 
+// 事件钩子：单个动作或动作列表，只支持 spawn / shell
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum HookEntry {
+    /// window_opened = { action = "shell", cmd = "..." }
+    Action(ActionConfig),
+    /// window_opened = [ { action = "..." }, { action = "..." } ]
+    List(Vec<ActionConfig>),
+}
+
 // 5. 根配置结构体
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
@@ -130,9 +197,13 @@ pub struct Config {
     pub pointer: Option<HashMap<String, KeyBindingEntry>>,
     pub resize: Option<HashMap<String, KeyBindingEntry>>,
     pub waybar: Option<WaybarConfig>,
+    pub status: Option<StatusConfig>,
     pub animations: Option<AnimationsConfig>,
     pub output: Option<HashMap<String, OutputConfig>>,
     pub window: Option<WindowConfig>,
+    pub layout: Option<LayoutConfig>,
+    pub hooks: Option<HashMap<String, HookEntry>>,
+    pub scratchpad: Option<HashMap<String, ScratchpadConfig>>,
 }
 
 impl Config {
@@ -176,9 +247,13 @@ impl Config {
             resize: None,
             pointer: None,
             waybar: None,
+            status: None,
             animations: None,
             output: None,
             window: None,
+            layout: None,
+            hooks: None,
+            scratchpad: None,
         }
     }
 }
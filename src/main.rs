@@ -4,6 +4,7 @@ pub mod config;
 pub mod protocol;
 pub mod wm;
 use crate::config::Config;
+use crate::wm::status::StatusFormat;
 use crate::wm::AppState;
 use std::collections::HashMap;
 use std::fs;
@@ -19,14 +20,30 @@ fn main() {
     // --- 使用 match 处理参数 ---
     if args.len() > 1 {
         match args[1].as_str() {
-            "--waybar" => {
-                run_waybar_client();
+            "--waybar" | "--status" => {
+                // 可选：--output <NAME> 只跟踪某一个显示器，--format <FMT> 选择输出格式，
+                // --tag <N> 只输出某一个标签 (用于可点击的单标签按钮)
+                let flag_value = |flag: &str| {
+                    args.iter()
+                        .position(|a| a == flag)
+                        .and_then(|i| args.get(i + 1))
+                        .cloned()
+                };
+                run_status_client(
+                    flag_value("--output"),
+                    flag_value("--format"),
+                    flag_value("--tag"),
+                );
                 return;
             }
             "--appid" => {
                 run_appid_client();
                 return;
             }
+            "msg" => {
+                run_msg_client(&args[2..]);
+                return;
+            }
             "--help" | "-h" => {
                 print_help();
                 return;
@@ -46,10 +63,8 @@ fn main() {
     let mut event_queue = conn.new_event_queue();
     let qh = event_queue.handle();
 
-    let display_name = std::env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".to_string());
-
     // Socket 1: Waybar 广播
-    let socket_path = format!("/tmp/rrwm-{}.sock", display_name);
+    let socket_path = ipc_socket_path("");
     let _ = fs::remove_file(&socket_path);
     let listener = UnixListener::bind(&socket_path).expect("Unable to create IPC Socket");
     listener
@@ -57,7 +72,7 @@ fn main() {
         .expect("Unable to set Socket non-blocking");
 
     // Socket 2: 指令查询
-    let cmd_socket_path = format!("/tmp/rrwm-{}-cmd.sock", display_name);
+    let cmd_socket_path = ipc_socket_path("-cmd");
     let _ = fs::remove_file(&cmd_socket_path);
     let cmd_listener =
         UnixListener::bind(&cmd_socket_path).expect("Unable to create Command Socket");
@@ -101,9 +116,15 @@ fn main() {
         heads: Vec::new(),
         last_output_serial: 0,
         layout_roots: HashMap::new(),
+        tag_layouts: HashMap::new(),
+        scroll_offsets: HashMap::new(),
+        column_widths: HashMap::new(),
+        grid_counts: HashMap::new(),
+        presel: None,
+        size_hints: HashMap::new(),
+        gap_overrides: HashMap::new(),
         focused_output: None,
         pending_pointer_warp: None,
-        last_sent_json: String::new(),
         anonymous_ls_outputs: Vec::new(),
         wl_name_to_monitor_name: HashMap::new(),
         active_river_outputs: Vec::new(),
@@ -119,10 +140,24 @@ fn main() {
         pending_op_start: false,
         pending_op_end: false,
         minimized_slots: HashMap::new(),
+        scratchpads: HashMap::new(),
+        pending_scratchpads: HashMap::new(),
+        swallowed: HashMap::new(),
+        focus_mru: Vec::new(),
+        mru_cycle: None,
         anim_start_time: None,
         tag_anim_direction: None,
         tag_anim_old_mask: 0,
+        event_subscribers: Vec::new(),
+        last_event_snapshot: Default::default(),
+        session: None,
+        last_session_dump: String::new(),
+        session_dirty: false,
+        pending_presets: HashMap::new(),
     };
+    // 上一次运行留下的状态文件：之后出现的窗口会回到原来的标签与位置
+    state.load_session();
+    state.check_hook_names();
 
     let _registry = display.get_registry(&qh, ());
     info!("rrwm has started and is listening for events...");
@@ -221,7 +256,7 @@ fn main() {
             // --- 情况 C: 指令查询进来 (rrwm --appid) ---
             if fds[2].revents & libc::POLLIN != 0 {
                 // 这里直接处理，不用等 ManageStart
-                state.handle_command_connections();
+                state.handle_command_connections(&qh);
             }
         } else {
             // poll 出错或意外唤醒
@@ -230,19 +265,81 @@ fn main() {
     }
 }
 
-/// 客户端模式：连接 Socket 并把收到的东西直接打印出来
-fn run_waybar_client() {
+/// 根据 WAYLAND_DISPLAY 拼出 Socket 路径，suffix 为 "" (广播) 或 "-cmd" (指令)
+fn ipc_socket_path(suffix: &str) -> String {
     let display_name = std::env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".to_string());
-    let socket_path = format!("/tmp/rrwm-{}.sock", display_name);
+    format!("/tmp/rrwm-{}{}.sock", display_name, suffix)
+}
+
+/// 客户端模式：连接 Socket 并把收到的东西直接打印出来
+/// 指定 output、format 或 tag 时改为通过指令 Socket 订阅
+fn run_status_client(output: Option<String>, format: Option<String>, tag: Option<String>) {
+    let tag = tag.map(|t| {
+        t.parse::<u32>().unwrap_or_else(|_| {
+            eprintln!("Error: --tag expects a tag number, got {}", t);
+            std::process::exit(1);
+        })
+    });
+    let subscribe = output.is_some() || format.is_some() || tag.is_some();
+    let stream = if subscribe {
+        UnixStream::connect(ipc_socket_path("-cmd")).and_then(|mut stream| {
+            let mut request = serde_json::json!({ "action": "subscribe_status" });
+            if let Some(name) = &output {
+                request["args"] = serde_json::json!([name]);
+            }
+            if let Some(f) = &format {
+                request["format"] = serde_json::Value::String(f.clone());
+            }
+            if let Some(t) = tag {
+                request["tag"] = serde_json::json!(t);
+            }
+            stream.write_all(format!("{}\n", request).as_bytes())?;
+            Ok(stream)
+        })
+    } else {
+        UnixStream::connect(ipc_socket_path(""))
+    };
 
-    if let Ok(stream) = UnixStream::connect(&socket_path) {
+    if let Ok(stream) = stream {
         let mut reader = BufReader::new(stream);
         let mut line = String::new();
 
+        // 订阅模式下第一行是指令回执，不是状态
+        if subscribe && reader.read_line(&mut line).unwrap_or(0) > 0 {
+            let reply: serde_json::Value = serde_json::from_str(&line).unwrap_or_default();
+            if reply["success"].as_bool() != Some(true) {
+                eprintln!(
+                    "Error: {}",
+                    reply["error"]
+                        .as_str()
+                        .unwrap_or("Unexpected reply from rrwm")
+                );
+                std::process::exit(1);
+            }
+            line.clear();
+        }
+
+        // i3bar 协议：先输出头部，再输出一个无限数组
+        // 与服务端一样解析格式名 (不区分大小写)
+        let is_i3bar = matches!(
+            format.as_deref().map(StatusFormat::parse),
+            Some(Ok(StatusFormat::I3bar))
+        );
+        if is_i3bar {
+            println!("{{\"version\":1}}");
+            println!("[");
+        }
+
         // 持续读取 Socket 里的每一行并打印到 stdout
         // Waybar 的 custom/script 模块会自动捕获这个 stdout
+        let mut first = true;
         while reader.read_line(&mut line).unwrap_or(0) > 0 {
+            if is_i3bar && !first {
+                print!(",");
+            }
             print!("{}", line);
+            let _ = std::io::stdout().flush();
+            first = false;
             line.clear();
         }
     } else {
@@ -253,8 +350,7 @@ fn run_waybar_client() {
 
 // --- 查询客户端实现 ---
 fn run_appid_client() {
-    let display_name = std::env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".to_string());
-    let socket_path = format!("/tmp/rrwm-{}-cmd.sock", display_name); // 注意这里连的是 cmd socket
+    let socket_path = ipc_socket_path("-cmd"); // 注意这里连的是 cmd socket
 
     if let Ok(mut stream) = UnixStream::connect(&socket_path) {
         // 1. 发送查询指令
@@ -274,12 +370,141 @@ fn run_appid_client() {
     }
 }
 
+// --- 通用指令客户端：rrwm msg [--json] [--unit N] [--criteria JSON] [--output NAME] <action> [args...] ---
+fn run_msg_client(args: &[String]) {
+    let mut print_json = false;
+    let mut unit = None;
+    let mut criteria = None;
+    let mut output = None;
+
+    // 选项只认动作名之前的部分，动作名之后的参数原样传递 (例如 shell 命令里的 --json)
+    let mut iter = args.iter();
+    let mut words = Vec::new();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--json" => print_json = true,
+            "--unit" => unit = iter.next().cloned(),
+            "--criteria" => criteria = iter.next().cloned(),
+            "--output" => output = iter.next().cloned(),
+            _ => {
+                words.push(arg.clone());
+                words.extend(iter.by_ref().cloned());
+                break;
+            }
+        }
+    }
+
+    if words.is_empty() {
+        eprintln!("Error: Missing command for 'rrwm msg'");
+        eprintln!("Try 'rrwm --help' for usage information.");
+        std::process::exit(1);
+    }
+
+    // shell 动作的参数是一整串命令，其余动作按 args 数组传递
+    let action = words.remove(0);
+    let mut request = serde_json::json!({ "action": action });
+    if action == "shell" {
+        request["cmd"] = serde_json::Value::String(words.join(" "));
+    } else if !words.is_empty() {
+        request["args"] = serde_json::json!(words);
+    }
+    if let Some(u) = unit {
+        request["unit"] = serde_json::Value::String(u);
+    }
+    if let Some(o) = output {
+        request["output"] = serde_json::Value::String(o);
+    }
+    if let Some(c) = criteria {
+        match serde_json::from_str::<serde_json::Value>(&c) {
+            Ok(v) => request["criteria"] = v,
+            Err(e) => {
+                eprintln!("Error: Invalid criteria JSON: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut stream = match UnixStream::connect(ipc_socket_path("-cmd")) {
+        Ok(s) => s,
+        Err(_) => {
+            error!("Unable to connect to rrwm Command Socket. Is rrwm running?");
+            std::process::exit(1);
+        }
+    };
+
+    let mut packet = request.to_string();
+    packet.push('\n');
+    if let Err(e) = stream.write_all(packet.as_bytes()) {
+        error!("Failed to send command: {}", e);
+        std::process::exit(1);
+    }
+
+    // 回执固定占一行；subscribe 的后续事件也是逐行到达
+    let mut reader = BufReader::new(stream);
+    let mut response = String::new();
+    if let Err(e) = reader.read_line(&mut response) {
+        error!("Failed to read reply: {}", e);
+        std::process::exit(1);
+    }
+
+    let reply: serde_json::Value = match serde_json::from_str(&response) {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("Error: Unexpected reply from rrwm: {}", response.trim());
+            std::process::exit(1);
+        }
+    };
+    let success = reply["success"].as_bool().unwrap_or(false);
+
+    if print_json {
+        println!("{}", reply);
+    } else if success {
+        match &reply["data"] {
+            serde_json::Value::Null => {}
+            serde_json::Value::String(text) => print!("{}", text),
+            data => println!("{}", serde_json::to_string_pretty(data).unwrap_or_default()),
+        }
+    } else {
+        eprintln!(
+            "Error: {}",
+            reply["error"].as_str().unwrap_or("Unknown error")
+        );
+    }
+
+    if success && action == "subscribe" {
+        for line in reader.lines() {
+            match line {
+                Ok(event) => println!("{}", event),
+                Err(_) => break,
+            }
+        }
+    }
+
+    std::process::exit(if success { 0 } else { 1 });
+}
+
 // --- 帮助信息函数 ---
 fn print_help() {
     println!("Usage: rrwm [OPTIONS]");
+    println!(
+        "       rrwm msg [--json] [--unit <N>] [--criteria <JSON>] [--output <NAME>] <ACTION|QUERY> [ARGS...]"
+    );
     println!("");
     println!("Options:");
     println!("  --waybar    Run in Waybar client mode (receive JSON status stream)");
+    println!("  --status    Same as --waybar, meant to be used with --format");
+    println!("    --output <NAME>  Only follow the given output instead of the focused one");
+    println!("    --format <FMT>   Status format: waybar (default), i3bar, eww or plain");
+    println!("    --tag <N>        Only print tag N, for per-tag clickable bar buttons");
     println!("  --appid     List all active windows and their AppIDs");
     println!("  --help      Print this help message");
+    println!();
+    println!("Commands:");
+    println!("  msg         Send an action (e.g. 'focus 3') or a query (e.g. 'get_windows')");
+    println!("              to the running rrwm; exits non-zero if the command failed");
+    println!("    --json    Print the raw JSON reply");
+    println!("    --unit    Pass a unit value, e.g. 'rrwm msg --unit 20 grow_width'");
+    println!("    --criteria  Run the action on matching windows instead of the focused one,");
+    println!("                e.g. --criteria '{{\"app_id\":\"mpv\"}}' move 9");
+    println!("    --output  Focus the given output first, e.g. 'rrwm msg --output DP-1 focus 2'");
 }
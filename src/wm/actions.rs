@@ -1,9 +1,19 @@
+use crate::protocol::river_wm::river_window_v1::RiverWindowV1;
 use crate::protocol::wlr_output_management::zwlr_output_mode_v1::ZwlrOutputModeV1;
-use crate::wm::layout::{Direction, Geometry, LayoutNode, ResizeAxis, SplitType};
+use crate::wm::layout::{
+    arrange_tag, scroll_to_column, ContainerMode, Direction, Gaps, Geometry, LayoutMode,
+    LayoutNode, MasterLocation, Preselection, ResizeAxis, ScrollView, SizeHints, SplitPolicy,
+    SplitType, TagLayout, TreeOp, TreeScope,
+};
+use crate::wm::status::StatusFormat;
 use crate::wm::AppState;
 use crate::wm::OutputData;
+use crate::wm::StatusClient;
+use crate::wm::WindowData;
 use serde::Serialize;
-use std::io::{Read, Write};
+use std::collections::HashMap;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
 use tracing::{debug, error, info, warn};
 use wayland_backend::client::ObjectId; // 修复点：引入 ObjectId 类型
 use wayland_client::protocol::wl_output::Transform; // 旋转枚举
@@ -40,6 +50,7 @@ pub enum Action {
     SwitchFocusFloat, // 在悬浮和平铺窗口之间切换焦点
     Focus(Direction),
     FocusTag(u32),           // 切换到某个标签掩码
+    CycleTag(i32),           // 在已占用的标签间循环切换 (+1 下一个, -1 上一个)
     MoveToTag(u32),          // 将窗口移动到某个标签掩码
     Move(Direction),         // 统一处理方向性移动
     FocusOutput(Direction),  // 处理 left_output / right_output
@@ -54,9 +65,54 @@ pub enum Action {
     MoveInteractive,
     ResizeInteractive,
     ToggleMinimizeRestore(String),
+    SetLayout(LayoutMode),             // 切换当前标签的布局模式
+    SetMasterLocation(MasterLocation), // 主区放在哪一侧
+    MasterCount(i32),                  // 主区窗口数增减
+    PromoteMaster,                     // 把窗口换到主区第一位
+    SetContainerMode(ContainerMode),   // 窗口所在容器改为 split / tabbed / stacked
+    CycleColumnWidth,                  // scroller 布局：窗口所在列切换到下一个预设宽度
+    Presel(Direction, Option<f32>),    // 预选下一个新窗口放在焦点窗口的哪一侧 (及占比)
+    CancelPresel,                      // 取消预选
+    TreeOp(TreeOp, TreeScope),         // 旋转/镜像/均分/切换方向，作用于父容器或整个标签
+    SaveLayout(String),                // 把当前标签的树保存为命名预设
+    LoadLayout(String),                // 把命名预设套用到当前标签
+    AdjustGaps(i32, i32),              // 当前标签的内/外间隙增减 (像素)
+    ResetGaps,                         // 当前标签的间隙恢复为配置值
+    ToggleScratchpad(String),          // 显示/隐藏/启动 [scratchpad.<名字>]
+    ToggleSticky,                      // 窗口在所有标签上显示 (会先变成悬浮窗)
+    Mark(String),                      // 给窗口打上标记 (同名标记只属于一个窗口)
+    Unmark,                            // 去掉窗口的标记
+    FocusMark(String),                 // 跳到带标记的窗口，需要时切换显示器和标签
+    SwapWithMark(String),              // 与带标记的窗口互换位置
+    FocusLast,                         // 回到上一个聚焦的窗口 (跨标签、跨显示器)
+    CycleMru(i32),                     // 按最近使用顺序循环切换窗口 (+1 更早, -1 更近)
 }
 
 impl Action {
+    /// 是否是作用于某个窗口的动作 (可以配合 IPC criteria 指定目标窗口)
+    pub fn targets_window(&self) -> bool {
+        matches!(
+            self,
+            Action::CloseFocused
+                | Action::ToggleFullscreen
+                | Action::ToggleFloat
+                | Action::MoveToTag(_)
+                | Action::Move(_)
+                | Action::MoveToOutput(_)
+                | Action::MoveStep(_, _)
+                | Action::ToggleMinimizeRestore(_)
+                | Action::PromoteMaster
+                | Action::SetContainerMode(_)
+                | Action::CycleColumnWidth
+                | Action::Presel(_, _)
+                | Action::TreeOp(_, _)
+                | Action::ToggleSticky
+                | Action::Mark(_)
+                | Action::Unmark
+                | Action::SwapWithMark(_)
+        )
+    }
+
     /// 核心逻辑：把 TOML 里的字符串配置变成代码里的枚举
     pub fn from_config(
         name: &str,
@@ -65,13 +121,27 @@ impl Action {
         unit: &Option<String>,
         slot_id: &str,
     ) -> Self {
+        Self::parse(name, args, cmd, unit, slot_id).unwrap_or_else(|e| {
+            warn!("Warning: {}", e);
+            Action::Shell("true".to_string())
+        })
+    }
+
+    /// 严格解析：未知动作名返回错误，供 IPC 回报给调用方
+    pub fn parse(
+        name: &str,
+        args: &Option<Vec<String>>,
+        cmd: &Option<String>,
+        unit: &Option<String>,
+        slot_id: &str,
+    ) -> Result<Self, String> {
         // 解析 unit，默认为 10 (如果配置了 resize 动作但没写 unit)
         let step = unit
             .as_ref()
             .and_then(|s| s.parse::<i32>().ok())
             .unwrap_or(10);
 
-        match name.to_lowercase().as_str() {
+        let action = match name.to_lowercase().as_str() {
             // --- 内部指令：关闭窗口 ---
             "close_window" | "close_focused" => Action::CloseFocused,
             // --- 内部指令：全屏切换 ---
@@ -117,13 +187,7 @@ impl Action {
                     "right" => Action::Focus(Direction::Right),
                     "up" => Action::Focus(Direction::Up),
                     "down" => Action::Focus(Direction::Down),
-                    _ => {
-                        if let Ok(idx) = arg.parse::<u32>() {
-                            Action::FocusTag(1 << (idx.saturating_sub(1)))
-                        } else {
-                            Action::Focus(Direction::Right)
-                        }
-                    }
+                    _ => Action::FocusTag(Self::tag_mask(name, arg)?),
                 }
             }
             "move" => {
@@ -168,23 +232,194 @@ impl Action {
                         }
                     }
 
-                    _ => {
-                        if let Ok(idx) = arg.parse::<u32>() {
-                            Action::MoveToTag(1 << (idx.saturating_sub(1)))
-                        } else {
-                            Action::Move(Direction::Right)
-                        }
+                    _ => Action::MoveToTag(Self::tag_mask(name, arg)?),
+                }
+            }
+            // --- 布局模式：tree / master_stack ---
+            "set_layout" => {
+                let arg = args.as_ref().and_then(|v| v.first()).map(|s| s.as_str());
+                match arg {
+                    Some(mode) => Action::SetLayout(LayoutMode::parse(mode)?),
+                    None => return Err("set_layout needs a layout name".to_string()),
+                }
+            }
+            "set_master_location" => {
+                let arg = args.as_ref().and_then(|v| v.first()).map(|s| s.as_str());
+                match arg {
+                    Some(loc) => Action::SetMasterLocation(MasterLocation::parse(loc)?),
+                    None => return Err("set_master_location needs a location".to_string()),
+                }
+            }
+            "inc_master_count" => Action::MasterCount(1),
+            "dec_master_count" => Action::MasterCount(-1),
+            "promote_master" | "zoom" => Action::PromoteMaster,
+            "cycle_column_width" => Action::CycleColumnWidth,
+            "set_container_mode" => {
+                let arg = args.as_ref().and_then(|v| v.first()).map(|s| s.as_str());
+                match arg {
+                    Some(mode) => Action::SetContainerMode(ContainerMode::parse(mode)?),
+                    None => return Err("set_container_mode needs a mode".to_string()),
+                }
+            }
+
+            // --- 手动预选：presel left/right/up/down [比例]，presel cancel 取消 ---
+            "presel" | "preselect" => {
+                let dir = args.as_ref().and_then(|v| v.first()).map(|s| s.as_str());
+                let ratio = args
+                    .as_ref()
+                    .and_then(|v| v.get(1))
+                    .map(|s| {
+                        s.parse::<f32>()
+                            .map(|r| r.clamp(0.05, 0.95))
+                            .map_err(|_| format!("Invalid presel ratio {}", s))
+                    })
+                    .transpose()?;
+                match dir {
+                    Some("left") => Action::Presel(Direction::Left, ratio),
+                    Some("right") => Action::Presel(Direction::Right, ratio),
+                    Some("up") => Action::Presel(Direction::Up, ratio),
+                    Some("down") => Action::Presel(Direction::Down, ratio),
+                    Some("cancel") => Action::CancelPresel,
+                    Some(other) => return Err(format!("Unknown presel direction {}", other)),
+                    None => return Err("presel needs a direction".to_string()),
+                }
+            }
+            "cancel_presel" => Action::CancelPresel,
+
+            // --- 树结构调整：rotate / flip / balance 的最后一个参数可选 parent (默认) 或 tag ---
+            "rotate" | "flip" | "balance" | "toggle_split" => {
+                let list = args.clone().unwrap_or_default();
+                let arg = |i: usize| list.get(i).map(|s| s.to_lowercase());
+                let (op, scope_arg) = match name.to_lowercase().as_str() {
+                    "rotate" => {
+                        let clockwise = match arg(0).as_deref().unwrap_or("90") {
+                            "90" | "cw" | "clockwise" => true,
+                            "-90" | "270" | "ccw" | "counterclockwise" => false,
+                            other => return Err(format!("Unknown rotate angle {}", other)),
+                        };
+                        (TreeOp::Rotate(clockwise), arg(1))
+                    }
+                    "flip" => {
+                        let axis = match arg(0).as_deref().unwrap_or("horizontal") {
+                            "horizontal" | "h" => ResizeAxis::Horizontal,
+                            "vertical" | "v" => ResizeAxis::Vertical,
+                            other => return Err(format!("Unknown flip axis {}", other)),
+                        };
+                        (TreeOp::Flip(axis), arg(1))
                     }
+                    "balance" => (TreeOp::Balance, arg(0)),
+                    _ => (TreeOp::ToggleSplit, None),
+                };
+                let scope = match scope_arg {
+                    Some(s) => TreeScope::parse(&s)?,
+                    None => TreeScope::Parent,
+                };
+                Action::TreeOp(op, scope)
+            }
+
+            // --- 布局预设：save_layout / load_layout <名字> ---
+            "save_layout" | "load_layout" => {
+                let preset = args.as_ref().and_then(|v| v.first()).cloned();
+                let preset = match preset {
+                    Some(n) => n,
+                    None => return Err(format!("{} needs a layout name", name)),
+                };
+                crate::wm::presets::preset_path(&preset)?;
+                if name.eq_ignore_ascii_case("save_layout") {
+                    Action::SaveLayout(preset)
+                } else {
+                    Action::LoadLayout(preset)
+                }
+            }
+
+            // --- 间隙：grow_gaps / shrink_gaps [像素，默认 5] [inner | outer，默认两者] ---
+            "grow_gaps" | "shrink_gaps" => {
+                let list = args.clone().unwrap_or_default();
+                let amount = match list.first() {
+                    Some(s) => s
+                        .parse::<i32>()
+                        .map_err(|_| format!("Invalid gap amount {}", s))?,
+                    None => 5,
+                };
+                let delta = if name.eq_ignore_ascii_case("grow_gaps") {
+                    amount
+                } else {
+                    -amount
+                };
+                match list.get(1).map(|s| s.to_lowercase()).as_deref() {
+                    Some("inner") => Action::AdjustGaps(delta, 0),
+                    Some("outer") => Action::AdjustGaps(0, delta),
+                    Some("all") | None => Action::AdjustGaps(delta, delta),
+                    Some(other) => return Err(format!("Unknown gap kind {}", other)),
+                }
+            }
+            "reset_gaps" => Action::ResetGaps,
+            "toggle_sticky" | "sticky" => Action::ToggleSticky,
+
+            // --- 标记：mark / focus_mark / swap_with_mark <名字>，unmark ---
+            "mark" | "focus_mark" | "swap_with_mark" => {
+                let mark = match args.as_ref().and_then(|v| v.first()) {
+                    Some(m) if !m.is_empty() => m.clone(),
+                    _ => return Err(format!("{} needs a mark name", name)),
+                };
+                match name.to_lowercase().as_str() {
+                    "mark" => Action::Mark(mark),
+                    "focus_mark" => Action::FocusMark(mark),
+                    _ => Action::SwapWithMark(mark),
+                }
+            }
+            "unmark" => Action::Unmark,
+            "focus_last" => Action::FocusLast,
+
+            // --- 最近使用的窗口 (alt-tab)：next / prev ---
+            "cycle_mru" => {
+                let arg = args
+                    .as_ref()
+                    .and_then(|v| v.first())
+                    .map(|s| s.as_str())
+                    .unwrap_or("next");
+                match arg {
+                    "next" | "1" | "+1" => Action::CycleMru(1),
+                    "prev" | "-1" => Action::CycleMru(-1),
+                    _ => return Err(format!("Unknown cycle_mru direction {}", arg)),
+                }
+            }
+            "toggle_scratchpad" | "scratchpad" => {
+                let arg = args.as_ref().and_then(|v| v.first());
+                match arg {
+                    Some(pad) => Action::ToggleScratchpad(pad.clone()),
+                    None => return Err("toggle_scratchpad needs a scratchpad name".to_string()),
+                }
+            }
+
+            // --- 标签循环 (状态栏滚轮)：next / prev ---
+            "cycle_tag" => {
+                let arg = args
+                    .as_ref()
+                    .and_then(|v| v.first())
+                    .map(|s| s.as_str())
+                    .unwrap_or("next");
+                match arg {
+                    "next" | "1" | "+1" => Action::CycleTag(1),
+                    "prev" | "-1" => Action::CycleTag(-1),
+                    _ => return Err(format!("Unknown cycle_tag direction {}", arg)),
                 }
             }
             // "spawn" 模式：直接启动，不经过 sh
             "spawn" => Action::Spawn(args.clone().unwrap_or_default()),
             // "shell" 模式：交给 sh -c 处理复杂逻辑
             "shell" => Action::Shell(cmd.clone().unwrap_or_default()),
-            _ => {
-                warn!("Warning: Unknown action name {}", name);
-                Action::Shell("true".to_string())
-            }
+            _ => return Err(format!("Unknown action name {}", name)),
+        };
+        Ok(action)
+    }
+
+    /// focus / move 的标签参数：1..=32 的序号转成掩码，其余一律报错
+    fn tag_mask(name: &str, arg: &str) -> Result<u32, String> {
+        match arg.parse::<u32>() {
+            Ok(idx) if (1..=32).contains(&idx) => Ok(1 << (idx - 1)),
+            Ok(idx) => Err(format!("Tag {} is out of range (1-32)", idx)),
+            Err(_) => Err(format!("Unknown {} target {}", name, arg)),
         }
     }
 }
@@ -402,30 +637,30 @@ impl AppState {
                 if !is_floating {
                     if let Some(old_root) = self.layout_roots.remove(&new_key) {
                         let new_root = match hint {
-                            MoveHint::Leftmost => LayoutNode::Container {
-                                split_type: SplitType::Vertical,
-                                ratio: 0.5,
-                                left_child: Box::new(LayoutNode::Window(wd)),
-                                right_child: Box::new(old_root),
-                            },
-                            MoveHint::Rightmost => LayoutNode::Container {
-                                split_type: SplitType::Vertical,
-                                ratio: 0.5,
-                                left_child: Box::new(old_root),
-                                right_child: Box::new(LayoutNode::Window(wd)),
-                            },
-                            MoveHint::Topmost => LayoutNode::Container {
-                                split_type: SplitType::Horizontal,
-                                ratio: 0.5,
-                                left_child: Box::new(LayoutNode::Window(wd)),
-                                right_child: Box::new(old_root),
-                            },
-                            MoveHint::Bottommost => LayoutNode::Container {
-                                split_type: SplitType::Horizontal,
-                                ratio: 0.5,
-                                left_child: Box::new(old_root),
-                                right_child: Box::new(LayoutNode::Window(wd)),
-                            },
+                            MoveHint::Leftmost => LayoutNode::split(
+                                SplitType::Vertical,
+                                0.5,
+                                LayoutNode::Window(wd),
+                                old_root,
+                            ),
+                            MoveHint::Rightmost => LayoutNode::split(
+                                SplitType::Vertical,
+                                0.5,
+                                old_root,
+                                LayoutNode::Window(wd),
+                            ),
+                            MoveHint::Topmost => LayoutNode::split(
+                                SplitType::Horizontal,
+                                0.5,
+                                LayoutNode::Window(wd),
+                                old_root,
+                            ),
+                            MoveHint::Bottommost => LayoutNode::split(
+                                SplitType::Horizontal,
+                                0.5,
+                                old_root,
+                                LayoutNode::Window(wd),
+                            ),
                         };
                         self.layout_roots.insert(new_key.clone(), new_root);
                     } else {
@@ -899,7 +1134,45 @@ impl AppState {
         false
     }
 
+    /// 依次执行一组动作，并处理重载配置带来的副作用（快捷键与 IPC 共用）
+    pub fn run_actions(&mut self, actions: Vec<Action>, qh: &QueueHandle<Self>) {
+        for action in actions {
+            // 其它动作打断 cycle_mru，当前选中的窗口就此确认
+            if !matches!(action, Action::CycleMru(_)) {
+                self.mru_cycle = None;
+            }
+            self.perform_action(action.clone());
+
+            if let Action::ReloadConfiguration = action {
+                let serial = self.last_output_serial;
+                self.apply_output_configs(qh, serial);
+            }
+        }
+
+        // --- 核心重载逻辑 ---
+        if self.needs_reload {
+            info!("-> Perform shortcut hot reload...");
+            // 1. 销毁旧对象：告诉 River 别再监听这些按键了
+            // drain(..) 会清空数组并返回里面的元素
+            for kb in self.key_bindings.drain(..) {
+                kb.obj.destroy();
+            }
+            // 2. 创建新对象：根据新 config 重新注册
+            crate::wm::binds::setup_keybindings(self, qh);
+            // 3. 强制通知：由于新绑定的 enable() 必须在 manage 序列执行
+            // 我们调用 manage_dirty() 强行让 River 发起一次 ManageStart
+            if let Some(wm) = &self.river_wm {
+                // info!("-> MANAGE_DIRTY TRIGGERED BY:Dispatch<RiverXkbConfigV1>");
+                wm.manage_dirty();
+            }
+            self.needs_reload = false;
+            info!("-> Hot reload completed!");
+        }
+    }
+
     pub fn perform_action(&mut self, action: Action) {
+        // 动作基本都会改动布局、标签或窗口状态，交给 save_session 比较后再决定是否写入
+        self.session_dirty = true;
         match action {
             // --- 切换 Resize 模式 ---
             Action::ToggleResizeMode => {
@@ -978,7 +1251,10 @@ impl AppState {
                                     },
                                 );
 
-                            if let Some(root) = self.layout_roots.get_mut(&tree_key) {
+                            if self.tag_layout(&tree_key).mode == LayoutMode::MasterStack {
+                                // 主从布局没有分割线，改为调整主区比例
+                                self.resize_master(&tree_key, &f_id, usable_area, axis, delta);
+                            } else if let Some(root) = self.layout_roots.get_mut(&tree_key) {
                                 root.apply_resize(&f_id, usable_area, axis, delta);
                             }
                         }
@@ -1130,40 +1406,12 @@ impl AppState {
                         if !is_floating {
                             // 如果是平铺窗，需要重新插入 BSP 树
                             let tree_key = (cur_out.clone(), cur_tags);
-                            if !self.layout_roots.contains_key(&tree_key) {
-                                self.layout_roots
-                                    .insert(tree_key.clone(), LayoutNode::Window(w_data.clone()));
-                            } else if let Some(mut root) = self.layout_roots.remove(&tree_key) {
-                                // 找当前焦点窗口作为切分目标
-                                let insert_target = self
-                                    .focused_window
-                                    .clone()
-                                    .unwrap_or_else(|| target_win_id.clone());
-
-                                let split =
-                                    if let Some(geo) = self.last_geometry.get(&insert_target) {
-                                        if geo.w > geo.h {
-                                            SplitType::Vertical
-                                        } else {
-                                            SplitType::Horizontal
-                                        }
-                                    } else {
-                                        SplitType::Vertical
-                                    };
-
-                                // 插入失败则强制合并到根节点
-                                if !root.insert_at(&insert_target, w_data.clone(), split, None) {
-                                    let new_root = LayoutNode::Container {
-                                        split_type: SplitType::Vertical,
-                                        ratio: 0.5,
-                                        left_child: Box::new(root),
-                                        right_child: Box::new(LayoutNode::Window(w_data.clone())),
-                                    };
-                                    self.layout_roots.insert(tree_key.clone(), new_root);
-                                } else {
-                                    self.layout_roots.insert(tree_key.clone(), root);
-                                }
-                            }
+                            // 找当前焦点窗口作为切分目标
+                            let insert_target = self
+                                .focused_window
+                                .clone()
+                                .unwrap_or_else(|| target_win_id.clone());
+                            self.insert_tiled(&tree_key, &insert_target, w_data.clone(), None);
                         }
 
                         // 夺取焦点，君临天下
@@ -1302,39 +1550,19 @@ impl AppState {
                             // --- Case B: 悬浮 -> 平铺 ---
                             info!("-> [Action] Window {:?} Switch to Tiling mode", f_id);
 
+                            // sticky 只对悬浮窗有意义，平铺后就只属于当前标签
+                            self.windows[idx].is_sticky = false;
+
                             // 如果树为空，作为根；否则插入到当前焦点历史或随机位置
                             let w_data = self.windows[idx].clone();
 
-                            if !self.layout_roots.contains_key(&tree_key) {
-                                self.layout_roots
-                                    .insert(tree_key, LayoutNode::Window(w_data));
-                            } else if let Some(mut root) = self.layout_roots.remove(&tree_key) {
-                                // 尝试插入到某个“参考窗口”旁边（比如最后活跃的平铺窗口
-                                let target_id = self
-                                    .tag_focus_history
-                                    .get(&tree_key)
-                                    .cloned()
-                                    .unwrap_or(f_id.clone());
-
-                                // 如果 insert_at 返回 false（没找到 target），我们就把 root 和新窗口组成一个新的 Container
-                                if !root.insert_at(
-                                    &target_id,
-                                    w_data.clone(),
-                                    SplitType::Vertical,
-                                    None,
-                                ) {
-                                    // 没找到插入点，强行合并
-                                    let new_root = LayoutNode::Container {
-                                        split_type: SplitType::Vertical,
-                                        ratio: 0.5,
-                                        left_child: Box::new(root),
-                                        right_child: Box::new(LayoutNode::Window(w_data)),
-                                    };
-                                    self.layout_roots.insert(tree_key, new_root);
-                                } else {
-                                    self.layout_roots.insert(tree_key, root);
-                                }
-                            }
+                            // 尝试插入到某个“参考窗口”旁边（比如最后活跃的平铺窗口）
+                            let target_id = self
+                                .tag_focus_history
+                                .get(&tree_key)
+                                .cloned()
+                                .unwrap_or(f_id.clone());
+                            self.insert_tiled(&tree_key, &target_id, w_data, None);
                         }
 
                         // 强制刷新
@@ -1419,9 +1647,11 @@ impl AppState {
             Action::ReloadConfiguration => {
                 info!("-> Reloading configuration manually...");
                 self.config = crate::config::Config::load();
+                self.check_hook_names();
                 self.needs_reload = true;
                 // self.current_keymap = None; // 启动了fcitx5的情况下重载布局会导致崩溃，
                 info!("-> The configuration has been reloaded and the new layout will take effect the next time the keyboard is accessed or manually triggered");
+                self.publish_event(&crate::wm::events::IpcEvent::ConfigReloaded);
             }
             Action::FocusOutput(dir) => self.cycle_output_focus(dir),
             Action::MoveToOutput(dir) => {
@@ -1468,6 +1698,97 @@ impl AppState {
                 }
             }
 
+            // --- 布局切换与主从布局参数 ---
+            Action::SetLayout(mode) => self.update_focused_layout(|l| l.mode = mode),
+            Action::SetMasterLocation(loc) => {
+                self.update_focused_layout(|l| l.master_location = loc)
+            }
+            Action::MasterCount(delta) => self.update_focused_layout(|l| {
+                l.master_count = (l.master_count as i32 + delta).max(0) as u32
+            }),
+            Action::PromoteMaster => {
+                if let Some(f_id) = self.focused_window.clone() {
+                    self.promote_master(&f_id);
+                }
+            }
+            Action::SetContainerMode(mode) => {
+                if let Some(f_id) = self.focused_window.clone() {
+                    self.set_container_mode(&f_id, mode);
+                }
+            }
+            Action::CycleColumnWidth => {
+                if let Some(f_id) = self.focused_window.clone() {
+                    self.cycle_column_width(&f_id);
+                }
+            }
+            Action::Presel(dir, ratio) => {
+                if let Some(f_id) = self.focused_window.clone() {
+                    self.preselect(&f_id, dir, ratio);
+                }
+            }
+            Action::TreeOp(op, scope) => {
+                if let Some(f_id) = self.focused_window.clone() {
+                    self.transform_tree(&f_id, op, scope);
+                }
+            }
+            Action::SaveLayout(name) => {
+                if let Err(e) = self.save_layout(&name) {
+                    warn!("-> [Layout] {}", e);
+                }
+            }
+            Action::LoadLayout(name) => {
+                if let Err(e) = self.load_layout(&name) {
+                    warn!("-> [Layout] {}", e);
+                }
+            }
+            Action::AdjustGaps(inner, outer) => self.adjust_gaps(inner, outer, false),
+            Action::ResetGaps => self.adjust_gaps(0, 0, true),
+            Action::ToggleScratchpad(name) => self.toggle_scratchpad(&name),
+            Action::ToggleSticky => {
+                if let Some(f_id) = self.focused_window.clone() {
+                    self.toggle_sticky(&f_id);
+                }
+            }
+            Action::Mark(name) => {
+                if let Some(f_id) = self.focused_window.clone() {
+                    self.set_mark(&f_id, &name);
+                }
+            }
+            Action::Unmark => {
+                if let Some(f_id) = self.focused_window.clone() {
+                    self.unmark(&f_id);
+                }
+            }
+            Action::FocusMark(name) => self.focus_mark(&name),
+            Action::FocusLast => self.focus_last(),
+            Action::CycleMru(delta) => self.cycle_mru(delta),
+            Action::SwapWithMark(name) => {
+                if let Some(f_id) = self.focused_window.clone() {
+                    self.swap_with_mark(&f_id, &name);
+                }
+            }
+            Action::CancelPresel => {
+                if self.presel.take().is_some() {
+                    info!("-> [Layout] Preselection cancelled");
+                    if let Some(wm) = &self.river_wm {
+                        wm.manage_dirty();
+                    }
+                }
+            }
+
+            // --- 标签循环 (状态栏滚轮) ---
+            Action::CycleTag(delta) => {
+                let dir = if delta > 0 {
+                    Direction::Right
+                } else {
+                    Direction::Left
+                };
+                self.cycle_tag(delta, dir);
+                if let Some(wm) = &self.river_wm {
+                    wm.manage_dirty();
+                }
+            }
+
             // --- 编号移动 (Super+Shift+数字) ---
             Action::MoveToTag(target_mask) => {
                 if let Some(f_id) = self.focused_window.clone() {
@@ -1547,7 +1868,11 @@ impl AppState {
 
                     let mut moved_locally = false;
                     if let Some(f_id) = &self.focused_window {
-                        if let Some(new_focus) = self.find_neighbor(f_id, dir) {
+                        // monocle / tabbed / stacked 先在藏起来的兄弟窗口间轮换，到头了再按方向找邻居
+                        if let Some(new_focus) = self
+                            .cycle_hidden_sibling(f_id, dir)
+                            .or_else(|| self.find_neighbor(f_id, dir))
+                        {
                             self.focused_window = Some(new_focus.clone());
                             if let Some(out_id) = self
                                 .windows
@@ -1584,21 +1909,62 @@ impl AppState {
         }
     }
 
+    /// 对指定窗口执行动作，而不是当前焦点窗口 (IPC criteria 使用)
+    /// 做法是临时把焦点指向目标窗口，执行完再把焦点还给原来的窗口
+    pub fn perform_action_on(&mut self, win_id: &ObjectId, action: Action) {
+        let prev_focus = self.focused_window.clone();
+        let was_focused = prev_focus.as_ref() == Some(win_id);
+        self.session_dirty = true;
+
+        match action {
+            // 脚本批量搬运窗口时不应该跟着切换标签
+            Action::MoveToTag(target_mask) => {
+                self.move_window_to_tag(win_id, target_mask, false, MoveHint::Leftmost);
+            }
+            // 每个窗口使用独立的插槽，再次匹配到同一窗口时即可还原
+            Action::ToggleMinimizeRestore(_) => {
+                let slot_id = format!("ipc_{}", win_id.protocol_id());
+                if !self.minimized_slots.contains_key(&slot_id) {
+                    self.focused_window = Some(win_id.clone());
+                }
+                self.perform_action(Action::ToggleMinimizeRestore(slot_id));
+            }
+            other => {
+                self.focused_window = Some(win_id.clone());
+                self.perform_action(other);
+            }
+        }
+
+        // 目标本来就是焦点时保持动作自身的焦点处理；被移出视图的情况由 ManageStart 找接班人
+        if !was_focused {
+            // 原焦点窗口仍然存在且没被收起，就把焦点还给它，否则同样交给 ManageStart
+            let prev_alive = prev_focus
+                .as_ref()
+                .is_some_and(|id| self.windows.iter().any(|w| &w.id == id && !w.is_minimized));
+            if prev_alive {
+                // 焦点没有真正离开原窗口，不需要 ManageStart 按层级找接班人
+                self.restrict_focus_to_tiling = false;
+                self.restrict_focus_to_floating = false;
+                self.focused_window = prev_focus;
+            } else {
+                self.focused_window = None;
+            }
+        }
+    }
+
     // --- 根据 Tag 查找动态图标 ---
-    fn get_dynamic_icon(&self, tag_index: u32) -> Option<String> {
+    pub fn get_dynamic_icon(&self, out_name: &str, tag_index: u32) -> Option<String> {
         let mask = 1 << tag_index;
-        // 以前端展示为主，基于当前聚焦的显示器来判断
-        let out_name = self.focused_output.as_ref()?;
         // 优先找焦点历史记录（用户最后操作过的那个窗口）
         let win_id = self
             .tag_focus_history
-            .get(&(out_name.clone(), mask))
+            .get(&(out_name.to_string(), mask))
             .cloned()
             .or_else(|| {
                 // 如果没有历史（比如刚启动），找该 Tag 下任意一个窗口
                 self.windows
                     .iter()
-                    .find(|w| w.output.as_ref() == Some(out_name) && (w.tags & mask) != 0)
+                    .find(|w| w.output.as_deref() == Some(out_name) && (w.tags & mask) != 0)
                     .map(|w| w.id.clone())
             });
 
@@ -1652,139 +2018,42 @@ impl AppState {
 
         final_icon
     }
-    /// 辅助：统一生成给 Waybar 的状态数据
-    fn get_waybar_response_json(&self) -> String {
-        let occupied = self.get_occupied_tags();
-        let waybar_cfg = self.config.waybar.as_ref();
-
-        let mut tag_strings = Vec::new();
-
-        // 1. 计算显示范围
-        let max_occupied_idx = if occupied == 0 {
-            0
-        } else {
-            32 - occupied.leading_zeros() - 1
-        };
-        let focused_idx = if self.focused_tags == 0 {
-            0
-        } else {
-            32 - self.focused_tags.leading_zeros() - 1
-        };
-        let visual_bound = (max_occupied_idx.max(focused_idx) + 1).min(31);
-
-        // 2. 循环生成每个标签的样式
-        for i in 0..=visual_bound {
-            let mask = 1 << i;
-
-            // --- 优先尝试获取动态图标 ---
-            let mut icon = self.get_dynamic_icon(i);
-
-            // 如果没有动态规则匹配，回退到 [waybar] tag_icons
-            if icon.is_none() {
-                icon = waybar_cfg
-                    .and_then(|c| c.tag_icons.as_ref())
-                    .and_then(|icons| icons.get(i as usize))
-                    .cloned();
+    /// 核心：处理 IPC 连接
+    pub fn handle_ipc_connections(&mut self) {
+        let mut streams = Vec::new();
+        if let Some(ref listener) = self.ipc_listener {
+            while let Ok((stream, _)) = listener.accept() {
+                //info!("-> IPC: Discover new listeners (Bar/Script)");
+                streams.push(stream);
             }
-
-            // 最后的保底：阿拉伯数字
-            let final_icon = icon.unwrap_or_else(|| (i + 1).to_string());
-
-            // --- 确定当前状态对应的样式前缀 ---
-            let style_prefix = if (self.focused_tags & mask) != 0 {
-                waybar_cfg.and_then(|c| c.focused_style.as_ref())
-            } else if (occupied & mask) != 0 {
-                waybar_cfg.and_then(|c| c.occupied_style.as_ref())
-            } else {
-                waybar_cfg.and_then(|c| c.empty_style.as_ref())
-            };
-
-            // --- 应用样式 ---
-            let styled_icon = match style_prefix {
-                Some(prefix) => format!("{}{}</span>", prefix, final_icon),
-                None => final_icon,
-            };
-
-            tag_strings.push(styled_icon);
         }
-
-        // 3. 构造最终的 Waybar 响应
-        let response = WaybarResponse {
-            text: tag_strings.join("  "),
-            tooltip: format!("Focus: {}", self.get_active_window_title()),
-            class: "rrwm-status".to_string(),
-        };
-
-        serde_json::to_string(&response).unwrap_or_default()
-    }
-
-    /// 核心：处理指令 Socket 连接 (如 rrwm --appid)
-    pub fn handle_command_connections(&mut self) {
-        if let Some(ref listener) = self.cmd_listener {
-            // accept() 是非阻塞的
-            while let Ok((mut stream, _)) = listener.accept() {
-                // 1. 读取指令
-                let mut buf = [0; 1024];
-                // 尝试读取，如果客户端连接了但没发数据，这里可能会 WouldBlock。
-                // 但对于本地 CLI 工具，通常数据是随连接瞬间到达的。
-                // 为了鲁棒性，我们简单尝试读取，读不到就忽略。
-                if let Ok(n) = stream.read(&mut buf) {
-                    let command = String::from_utf8_lossy(&buf[..n]).trim().to_string();
-
-                    // 2. 路由指令
-                    let response = match command.as_str() {
-                        "ls_clients" => self.get_app_ids_report(),
-                        _ => "Unknown command\n".to_string(),
-                    };
-
-                    // 3. 写回响应并关闭连接
-                    let _ = stream.write_all(response.as_bytes());
-                }
-            }
+        for stream in streams {
+            self.add_status_client(stream, None, None, StatusFormat::Waybar);
         }
     }
 
-    /// 辅助：生成 AppID 报告字符串
-    fn get_app_ids_report(&self) -> String {
-        let mut report = String::from("ID\tAppID\t\tTitle\t\t\tTag\n");
-        report.push_str("--\t-----\t\t-----\t\t\t---\n");
-
-        for w in &self.windows {
-            let app_id = w.app_id.as_deref().unwrap_or("<Unknown>");
-            let title = w.title.as_deref().unwrap_or("<None>");
-            let id_raw = w.id.protocol_id();
-            let extra = if w.is_fullscreen {
-                "[Fullscreen]"
-            } else if w.is_floating {
-                "[Floating]"
-            } else {
-                ""
-            };
-
-            // 加入 title 打印
-            report.push_str(&format!(
-                "{}\t{}\t\t{}\t\tTag:{:b} {}\n",
-                id_raw, app_id, title, w.tags, extra
-            ));
-        }
-
-        if self.windows.is_empty() {
-            report.push_str("(No windows)\n");
+    /// 登记状态栏客户端并立即推送一次当前状态
+    pub fn add_status_client(
+        &mut self,
+        mut stream: UnixStream,
+        output: Option<String>,
+        tag: Option<u32>,
+        format: StatusFormat,
+    ) {
+        // 状态栏不读数据时写满缓冲区就写失败被移除，不能阻塞 WM
+        if stream.set_nonblocking(true).is_err() {
+            return;
         }
-        report
-    }
-
-    /// 核心：处理 IPC 连接
-    pub fn handle_ipc_connections(&mut self) {
-        if let Some(ref listener) = self.ipc_listener {
-            while let Ok((mut stream, _)) = listener.accept() {
-                //info!("-> IPC: Discover new listeners (Bar/Script)");
-                let mut json = self.get_waybar_response_json();
-                json.push('\n');
-                let _ = Write::write_all(&mut stream, json.as_bytes());
-
-                self.ipc_clients.push(stream);
-            }
+        let json = self.render_status(output.as_deref(), tag, format);
+        let packet = format!("{}\n", json);
+        if Write::write_all(&mut stream, packet.as_bytes()).is_ok() {
+            self.ipc_clients.push(StatusClient {
+                stream,
+                output,
+                tag,
+                format,
+                last_sent: json,
+            });
         }
     }
 
@@ -1794,19 +2063,29 @@ impl AppState {
             return;
         }
 
-        let json_content = self.get_waybar_response_json();
-
-        // 【节流】只有内容变化时才真正写入 Socket
-        if json_content == self.last_sent_json {
-            return;
+        // 同一个显示器、同一个标签、同一种格式的客户端共用一次渲染结果
+        let mut rendered: HashMap<(Option<String>, Option<u32>, StatusFormat), String> =
+            HashMap::new();
+        for client in &self.ipc_clients {
+            rendered
+                .entry((client.output.clone(), client.tag, client.format))
+                .or_insert_with(|| {
+                    self.render_status(client.output.as_deref(), client.tag, client.format)
+                });
         }
-        self.last_sent_json = json_content.clone();
 
-        let mut packet = json_content;
-        packet.push('\n');
+        self.ipc_clients.retain_mut(|client| {
+            let json_content = &rendered[&(client.output.clone(), client.tag, client.format)];
 
-        self.ipc_clients
-            .retain_mut(|client| std::io::Write::write_all(client, packet.as_bytes()).is_ok());
+            // 【节流】只有内容变化时才真正写入 Socket
+            if *json_content == client.last_sent {
+                return true;
+            }
+            client.last_sent = json_content.clone();
+
+            let packet = format!("{}\n", json_content);
+            std::io::Write::write_all(&mut client.stream, packet.as_bytes()).is_ok()
+        });
     }
 
     /// 计算哪些标签有窗口
@@ -1925,30 +2204,30 @@ impl AppState {
             if !is_floating {
                 if let Some(old_root) = self.layout_roots.remove(&new_key) {
                     let new_root = match hint {
-                        MoveHint::Leftmost => LayoutNode::Container {
-                            split_type: SplitType::Vertical,
-                            ratio: 0.5,
-                            left_child: Box::new(LayoutNode::Window(w_data)),
-                            right_child: Box::new(old_root),
-                        },
-                        MoveHint::Rightmost => LayoutNode::Container {
-                            split_type: SplitType::Vertical,
-                            ratio: 0.5,
-                            left_child: Box::new(old_root),
-                            right_child: Box::new(LayoutNode::Window(w_data)),
-                        },
-                        MoveHint::Topmost => LayoutNode::Container {
-                            split_type: SplitType::Horizontal,
-                            ratio: 0.5,
-                            left_child: Box::new(LayoutNode::Window(w_data)),
-                            right_child: Box::new(old_root),
-                        },
-                        MoveHint::Bottommost => LayoutNode::Container {
-                            split_type: SplitType::Horizontal,
-                            ratio: 0.5,
-                            left_child: Box::new(old_root),
-                            right_child: Box::new(LayoutNode::Window(w_data)),
-                        },
+                        MoveHint::Leftmost => LayoutNode::split(
+                            SplitType::Vertical,
+                            0.5,
+                            LayoutNode::Window(w_data),
+                            old_root,
+                        ),
+                        MoveHint::Rightmost => LayoutNode::split(
+                            SplitType::Vertical,
+                            0.5,
+                            old_root,
+                            LayoutNode::Window(w_data),
+                        ),
+                        MoveHint::Topmost => LayoutNode::split(
+                            SplitType::Horizontal,
+                            0.5,
+                            LayoutNode::Window(w_data),
+                            old_root,
+                        ),
+                        MoveHint::Bottommost => LayoutNode::split(
+                            SplitType::Horizontal,
+                            0.5,
+                            old_root,
+                            LayoutNode::Window(w_data),
+                        ),
                     };
                     self.layout_roots.insert(new_key.clone(), new_root);
                 } else {
@@ -2063,8 +2342,11 @@ impl AppState {
             }
             return; // 悬浮窗处理完毕，直接返回
         }
-        // 1. 尝试在当前方向寻找邻居
-        if let Some(neighbor_id) = self.find_neighbor(win_id, dir) {
+        // 1. 尝试在当前方向寻找邻居 (monocle/tabbed/scroller 按窗口顺序找相邻的那个)
+        if let Some(neighbor_id) = self
+            .cycle_hidden_sibling(win_id, dir)
+            .or_else(|| self.find_neighbor(win_id, dir))
+        {
             debug!(
                 "-> Discover neighbor {:?} and perform location exchange",
                 neighbor_id
@@ -2143,6 +2425,658 @@ impl AppState {
             }
         }
     }
+    /// 标签的布局：运行时设置过的优先，其次是 [output.NAME] layout，最后是 [layout] 配置
+    pub fn tag_layout(&self, key: &(String, u32)) -> TagLayout {
+        if let Some(layout) = self.tag_layouts.get(key) {
+            return *layout;
+        }
+
+        let mut layout = TagLayout::default();
+        if let Some(cfg) = self.config.layout.as_ref() {
+            if let Some(mode) = cfg.mode.as_deref().and_then(|m| LayoutMode::parse(m).ok()) {
+                layout.mode = mode;
+            }
+            if let Some(n) = cfg
+                .master_count
+                .as_ref()
+                .and_then(|s| s.parse::<u32>().ok())
+            {
+                layout.master_count = n;
+            }
+            if let Some(r) = cfg
+                .master_ratio
+                .as_ref()
+                .and_then(|s| s.parse::<f32>().ok())
+            {
+                layout.master_ratio = r.clamp(0.05, 0.95);
+            }
+            if let Some(loc) = cfg
+                .master_location
+                .as_deref()
+                .and_then(|l| MasterLocation::parse(l).ok())
+            {
+                layout.master_location = loc;
+            }
+        }
+        if let Some(mode) = self
+            .config
+            .output
+            .as_ref()
+            .and_then(|outputs| outputs.get(&key.0))
+            .and_then(|o| o.layout.as_deref())
+            .and_then(|m| LayoutMode::parse(m).ok())
+        {
+            layout.mode = mode;
+        }
+        layout
+    }
+
+    /// 标签的切分策略：[window] tag_split_policies 按标签覆盖，其次 split_policy，默认 longest_side
+    pub fn split_policy(&self, key: &(String, u32)) -> SplitPolicy {
+        let cfg = match self.config.window.as_ref() {
+            Some(c) => c,
+            None => return SplitPolicy::LongestSide,
+        };
+        // 只有单个标签才有“标签序号”，同时显示多个标签时用全局策略
+        let per_tag = if key.1.count_ones() == 1 {
+            cfg.tag_split_policies
+                .as_ref()
+                .and_then(|map| map.get(&(key.1.trailing_zeros() + 1).to_string()))
+        } else {
+            None
+        };
+        per_tag
+            .or(cfg.split_policy.as_ref())
+            .and_then(|p| SplitPolicy::parse(p).ok())
+            .unwrap_or(SplitPolicy::LongestSide)
+    }
+
+    /// 按切分策略决定新窗口如何切分目标窗口：返回 (切割方向, 新窗口是否放在左/上)；
+    /// 目标窗口上有手动预选时以预选为准
+    pub fn policy_split(&self, key: &(String, u32), target_id: &ObjectId) -> (SplitType, bool) {
+        if let Some(presel) = self.presel.as_ref().filter(|p| &p.window == target_id) {
+            return match presel.dir {
+                Direction::Left => (SplitType::Vertical, true),
+                Direction::Right => (SplitType::Vertical, false),
+                Direction::Up => (SplitType::Horizontal, true),
+                Direction::Down => (SplitType::Horizontal, false),
+            };
+        }
+
+        let (ref_w, ref_h) = self
+            .last_geometry
+            .get(target_id)
+            .map(|g| (g.w, g.h))
+            .or_else(|| {
+                self.outputs
+                    .get(&key.0)
+                    .map(|o| (o.usable_area.w, o.usable_area.h))
+            })
+            .unwrap_or((1920, 1080));
+        let longest = if ref_w < ref_h {
+            SplitType::Horizontal
+        } else {
+            SplitType::Vertical
+        };
+
+        let path = self
+            .layout_roots
+            .get(key)
+            .and_then(|root| root.split_path(target_id))
+            .unwrap_or_default();
+        // dwindle/spiral 与父容器的方向交替，目标独占整棵树时按较长的一边
+        let alternate = match path.last() {
+            Some(SplitType::Vertical) => SplitType::Horizontal,
+            Some(SplitType::Horizontal) => SplitType::Vertical,
+            None => longest,
+        };
+
+        match self.split_policy(key) {
+            SplitPolicy::LongestSide | SplitPolicy::Grid => (longest, false),
+            SplitPolicy::Dwindle => (alternate, false),
+            // 每深一层旋转一次：右、下、左、上
+            SplitPolicy::Spiral => (alternate, path.len() % 4 >= 2),
+        }
+    }
+
+    /// 把平铺窗口插入标签的树：树为空时作为根，否则按切分策略切分目标窗口，
+    /// 找不到目标时和整棵树并排
+    pub fn insert_tiled(
+        &mut self,
+        key: &(String, u32),
+        target_id: &ObjectId,
+        w_data: WindowData,
+        custom_ratio: Option<f32>,
+    ) {
+        let (split, new_first) = self.policy_split(key, target_id);
+        // 预选用掉即清除，预选的比例优先于窗口规则算出的比例
+        let mut custom_ratio = custom_ratio;
+        if self.presel.as_ref().is_some_and(|p| &p.window == target_id) {
+            if let Some(ratio) = self.presel.take().and_then(|p| p.ratio) {
+                custom_ratio = Some(1.0 - ratio);
+            }
+        }
+        let new_root = match self.layout_roots.remove(key) {
+            None => LayoutNode::Window(w_data),
+            Some(mut root) => {
+                if root.insert_beside(target_id, w_data.clone(), split, custom_ratio, new_first) {
+                    root
+                } else {
+                    LayoutNode::split(
+                        SplitType::Vertical,
+                        custom_ratio.unwrap_or(0.5),
+                        root,
+                        LayoutNode::Window(w_data),
+                    )
+                }
+            }
+        };
+        self.layout_roots.insert(key.clone(), new_root);
+    }
+
+    /// 预选窗口旁边放下一个新窗口的位置；对同一窗口再次预选同一方向则取消 (bspwm 的习惯)
+    fn preselect(&mut self, win_id: &ObjectId, dir: Direction, ratio: Option<f32>) {
+        let is_tiled = self
+            .windows
+            .iter()
+            .any(|w| &w.id == win_id && !w.is_floating && !w.is_minimized);
+        if !is_tiled {
+            return;
+        }
+
+        let same = self
+            .presel
+            .as_ref()
+            .is_some_and(|p| &p.window == win_id && p.dir == dir && p.ratio == ratio);
+        if same {
+            info!("-> [Layout] Preselection cancelled");
+            self.presel = None;
+        } else {
+            info!(
+                "-> [Layout] Preselect {:?} of {:?} (ratio {:?})",
+                dir, win_id, ratio
+            );
+            self.presel = Some(Preselection {
+                window: win_id.clone(),
+                dir,
+                ratio,
+            });
+        }
+
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+
+    /// grid 策略的 tree 标签：窗口数量变化后按窗口顺序重建成均匀的网格
+    pub fn rebuild_grids(&mut self) {
+        let keys: Vec<(String, u32)> = self
+            .layout_roots
+            .keys()
+            .filter(|key| {
+                self.tag_layout(key).mode == LayoutMode::Tree
+                    && self.split_policy(key) == SplitPolicy::Grid
+            })
+            .cloned()
+            .collect();
+
+        for key in keys {
+            let windows: Vec<WindowData> = match self.layout_roots.get(&key) {
+                Some(root) => root.leaves().into_iter().cloned().collect(),
+                None => continue,
+            };
+            let count = windows.len();
+            if self.grid_counts.get(&key) == Some(&count) {
+                continue;
+            }
+            if let Some(grid) = LayoutNode::grid(windows) {
+                info!(
+                    "-> [Layout] Rebuilt grid for {:?} with {} windows",
+                    key, count
+                );
+                self.layout_roots.insert(key.clone(), grid);
+            }
+            self.grid_counts.insert(key, count);
+        }
+    }
+
+    /// 修改焦点显示器当前标签的布局参数，并记住它 (之后不再跟随配置默认值)
+    fn update_focused_layout(&mut self, apply: impl FnOnce(&mut TagLayout)) {
+        let out_id = match &self.focused_output {
+            Some(id) => id.clone(),
+            None => return,
+        };
+        let key = (out_id, self.focused_tags);
+        let mut layout = self.tag_layout(&key);
+        apply(&mut layout);
+        info!(
+            "-> [Layout] {} (Tag mask: {:b}) -> {:?}",
+            key.0, key.1, layout
+        );
+        self.tag_layouts.insert(key, layout);
+
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+
+    /// 把窗口换到主区第一位；它本来就在第一位时与第二个窗口交换 (类似 dwm 的 zoom)
+    fn promote_master(&mut self, win_id: &ObjectId) {
+        let tree_key = match self.windows.iter().find(|w| &w.id == win_id) {
+            Some(w) if !w.is_floating => match &w.output {
+                Some(out) => (out.clone(), w.tags),
+                None => return,
+            },
+            _ => return,
+        };
+
+        if let Some(root) = self.layout_roots.get_mut(&tree_key) {
+            let order: Vec<ObjectId> = root.leaves().iter().map(|w| w.id.clone()).collect();
+            let target = if order.first() == Some(win_id) {
+                order.get(1)
+            } else {
+                order.first()
+            };
+            if let Some(target) = target.cloned() {
+                LayoutNode::swap_windows(root, win_id, &target);
+            }
+        }
+
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+
+    /// 把窗口所在的容器改为 split / tabbed / stacked
+    fn set_container_mode(&mut self, win_id: &ObjectId, mode: ContainerMode) {
+        let tree_key = match self.windows.iter().find(|w| &w.id == win_id) {
+            Some(w) if !w.is_floating => match &w.output {
+                Some(out) => (out.clone(), w.tags),
+                None => return,
+            },
+            _ => return,
+        };
+
+        if let Some(root) = self.layout_roots.get_mut(&tree_key) {
+            if root.set_parent_mode(win_id, mode) {
+                info!("-> [Layout] Container of {:?} -> {:?}", win_id, mode);
+                root.remember_active(win_id);
+            } else {
+                info!("-> [Layout] {:?} has no container to change", win_id);
+            }
+        }
+
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+
+    /// 调整窗口所在的树：父容器或整个标签，位置变化会走普通的布局动画
+    fn transform_tree(&mut self, win_id: &ObjectId, op: TreeOp, scope: TreeScope) {
+        let tree_key = match self.windows.iter().find(|w| &w.id == win_id) {
+            Some(w) if !w.is_floating => match &w.output {
+                Some(out) => (out.clone(), w.tags),
+                None => return,
+            },
+            _ => return,
+        };
+
+        if let Some(root) = self.layout_roots.get_mut(&tree_key) {
+            let applied = match scope {
+                TreeScope::Tag => {
+                    root.apply_tree_op(op);
+                    true
+                }
+                TreeScope::Parent => root.apply_to_parent(win_id, op),
+            };
+            if applied {
+                info!("-> [Layout] {:?} ({:?}) around {:?}", op, scope, win_id);
+            } else {
+                info!("-> [Layout] {:?} has no container to change", win_id);
+            }
+        }
+
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+
+    /// 标签当前“应该显示”的窗口：焦点在这个标签上就是焦点窗口，否则是该标签记住的焦点
+    pub fn tag_active_window(&self, tree_key: &(String, u32)) -> Option<&ObjectId> {
+        let focused = self.focused_window.as_ref().filter(|f_id| {
+            self.windows.iter().any(|w| {
+                &w.id == *f_id && w.output.as_ref() == Some(&tree_key.0) && w.tags == tree_key.1
+            })
+        });
+        focused.or_else(|| self.tag_focus_history.get(tree_key))
+    }
+
+    /// 所有标签里被 monocle 或 tabbed/stacked 容器藏起来的平铺窗口，以及 scroller 视口外的列
+    pub fn hidden_tiled_windows(&self) -> Vec<ObjectId> {
+        self.layout_roots
+            .iter()
+            .flat_map(|(key, root)| {
+                let mode = self.tag_layout(key).mode;
+                let mut hidden = root.hidden_windows(mode, self.tag_active_window(key));
+                if mode == LayoutMode::Scroller {
+                    if let Some(out) = self.outputs.get(&key.0) {
+                        let area = out.usable_area;
+                        hidden.extend(
+                            self.arrange_tag_windows(key, area)
+                                .into_iter()
+                                .filter(|(_, g)| g.x + g.w <= area.x || g.x >= area.x + area.w)
+                                .map(|(window, _)| window.id()),
+                        );
+                    }
+                }
+                hidden
+            })
+            .collect()
+    }
+
+    /// 计算某个标签里平铺窗口的位置 (各种布局模式的统一入口)
+    pub fn arrange_tag_windows(
+        &self,
+        tree_key: &(String, u32),
+        area: Geometry,
+    ) -> Vec<(RiverWindowV1, Geometry)> {
+        let mut results = Vec::new();
+        if let Some(root) = self.layout_roots.get(tree_key) {
+            let layout = self.tag_layout(tree_key);
+            let scroll = self.scroll_view(tree_key, area);
+            let hints = self.tiled_size_hints(tree_key);
+            arrange_tag(root, &layout, area, &scroll, &hints, &mut results);
+        }
+        results
+    }
+
+    /// 布局用的尺寸限制：客户端声明的是窗口本身的尺寸，布局切出的格子还要算上四周的间隙
+    pub fn tiled_size_hints(&self, tree_key: &(String, u32)) -> HashMap<ObjectId, SizeHints> {
+        let gaps = self.gaps_for(tree_key);
+        let half = (gaps.inner as f32 / 2.0).ceil() as i32;
+        let pad_w = gaps.left.max(half) + gaps.right.max(half);
+        let pad_h = gaps.top.max(half) + gaps.bottom.max(half);
+        let grow = |v: i32, pad: i32| if v > 0 { v + pad } else { 0 };
+        self.size_hints
+            .iter()
+            .map(|(id, h)| {
+                let padded = SizeHints {
+                    min_w: grow(h.min_w, pad_w),
+                    min_h: grow(h.min_h, pad_h),
+                    max_w: grow(h.max_w, pad_w),
+                    max_h: grow(h.max_h, pad_h),
+                };
+                (id.clone(), padded)
+            })
+            .collect()
+    }
+
+    /// 标签的间隙：[window] 为默认，[output.<名字>] 与 [window.tag_gaps] 依次覆盖；
+    /// 运行时调整过的标签直接用调整后的值。都不小于边框宽度，保证边框画得下
+    pub fn gaps_for(&self, tree_key: &(String, u32)) -> Gaps {
+        if let Some(gaps) = self.gap_overrides.get(tree_key) {
+            return *gaps;
+        }
+        let win_cfg = self.config.window.as_ref();
+        let base = win_cfg
+            .and_then(|c| c.gaps.as_ref())
+            .and_then(|s| s.parse::<i32>().ok())
+            .unwrap_or(0);
+        let mut inner = base;
+        let mut outer = [base; 4];
+
+        // 只有单个标签才有“标签序号”，同时显示多个标签时不套用按标签的设置
+        let tag_cfg = win_cfg
+            .and_then(|c| c.tag_gaps.as_ref())
+            .filter(|_| tree_key.1.count_ones() == 1)
+            .and_then(|map| map.get(&(tree_key.1.trailing_zeros() + 1).to_string()));
+        let out_cfg = self
+            .config
+            .output
+            .as_ref()
+            .and_then(|outputs| outputs.get(&tree_key.0));
+        let layers = [
+            win_cfg.map(|c| (&c.inner_gaps, &c.outer_gaps)),
+            out_cfg.map(|c| (&c.inner_gaps, &c.outer_gaps)),
+            tag_cfg.map(|c| (&c.inner_gaps, &c.outer_gaps)),
+        ];
+        for (inner_cfg, outer_cfg) in layers.into_iter().flatten() {
+            if let Some(n) = inner_cfg.as_ref().and_then(|s| s.parse::<i32>().ok()) {
+                inner = n;
+            }
+            if let Some(value) = outer_cfg {
+                match Gaps::parse_outer(value) {
+                    Ok(sides) => outer = sides,
+                    Err(e) => warn!("-> [Layout] {}", e),
+                }
+            }
+        }
+
+        let border = self.border_width();
+        Gaps {
+            inner: inner.max(border),
+            top: outer[0].max(border),
+            right: outer[1].max(border),
+            bottom: outer[2].max(border),
+            left: outer[3].max(border),
+        }
+    }
+
+    /// 聚焦窗口的边框宽度，来自 [window.active] border
+    fn border_width(&self) -> i32 {
+        self.config
+            .window
+            .as_ref()
+            .and_then(|c| c.active.as_ref())
+            .and_then(|a| a.border.as_ref())
+            .and_then(|b| b.width.parse::<i32>().ok())
+            .unwrap_or(0)
+    }
+
+    /// 运行时增减当前标签的间隙 (内, 外)；reset 时回到配置里的值
+    fn adjust_gaps(&mut self, inner: i32, outer: i32, reset: bool) {
+        let out_id = match &self.focused_output {
+            Some(id) => id.clone(),
+            None => return,
+        };
+        let key = (out_id, self.focused_tags);
+        if reset {
+            self.gap_overrides.remove(&key);
+        } else {
+            let border = self.border_width();
+            let mut gaps = self.gaps_for(&key);
+            gaps.inner = (gaps.inner + inner).max(border);
+            for side in [
+                &mut gaps.top,
+                &mut gaps.right,
+                &mut gaps.bottom,
+                &mut gaps.left,
+            ] {
+                *side = (*side + outer).max(border);
+            }
+            self.gap_overrides.insert(key.clone(), gaps);
+        }
+        info!(
+            "-> [Layout] {} (Tag mask: {:b}) gaps -> {:?}",
+            key.0,
+            key.1,
+            self.gaps_for(&key)
+        );
+
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+
+    /// scroller 列宽预设 (占屏幕比例)，来自 [layout] column_widths，默认 1/2、2/3、1/3
+    fn column_width_presets(&self, total_px: i32) -> Vec<f32> {
+        let presets: Vec<f32> = self
+            .config
+            .layout
+            .as_ref()
+            .and_then(|c| c.column_widths.as_ref())
+            .map(|list| {
+                list.iter()
+                    .map(|s| Self::parse_dimension_ratio(s, total_px).clamp(0.05, 1.0))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if presets.is_empty() {
+            vec![0.5, 2.0 / 3.0, 1.0 / 3.0]
+        } else {
+            presets
+        }
+    }
+
+    /// 某个标签的滚动视口：未调整过的列使用第一个预设宽度
+    fn scroll_view(&self, tree_key: &(String, u32), area: Geometry) -> ScrollView {
+        let default_ratio = self.column_width_presets(area.w)[0];
+        ScrollView {
+            column_widths: self
+                .layout_roots
+                .get(tree_key)
+                .map(|root| {
+                    root.leaves()
+                        .iter()
+                        .map(|w| {
+                            let ratio = self.column_widths.get(&w.id).copied();
+                            (area.w as f32 * ratio.unwrap_or(default_ratio)) as i32
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            offset: self.scroll_offsets.get(tree_key).copied().unwrap_or(0),
+        }
+    }
+
+    /// scroller 标签的视口滚到当前焦点 (或该标签记住的焦点) 所在的列
+    pub fn update_scroll_offsets(&mut self) {
+        let keys: Vec<(String, u32)> = self
+            .layout_roots
+            .keys()
+            .filter(|key| self.tag_layout(key).mode == LayoutMode::Scroller)
+            .cloned()
+            .collect();
+
+        for key in keys {
+            let area = match self.outputs.get(&key.0) {
+                Some(out) => out.usable_area,
+                None => continue,
+            };
+            let scroll = self.scroll_view(&key, area);
+            let index = self.tag_active_window(&key).and_then(|id| {
+                self.layout_roots
+                    .get(&key)
+                    .and_then(|root| root.leaves().iter().position(|w| &w.id == id))
+            });
+            let offset = scroll_to_column(&scroll, index.unwrap_or(0), area.w);
+            self.scroll_offsets.insert(key, offset);
+        }
+    }
+
+    /// scroller 布局：把窗口所在的列切换到下一个预设宽度
+    fn cycle_column_width(&mut self, win_id: &ObjectId) {
+        let total_px = match self
+            .windows
+            .iter()
+            .find(|w| &w.id == win_id)
+            .and_then(|w| w.output.as_ref())
+            .and_then(|out| self.outputs.get(out))
+        {
+            Some(out) => out.usable_area.w,
+            None => return,
+        };
+        let presets = self.column_width_presets(total_px);
+        let current = self
+            .column_widths
+            .get(win_id)
+            .copied()
+            .unwrap_or(presets[0]);
+        let next = presets
+            .iter()
+            .position(|p| (p - current).abs() < 0.001)
+            .map(|i| presets[(i + 1) % presets.len()])
+            .unwrap_or(presets[0]);
+
+        info!("-> [Layout] Column width of {:?} -> {:.3}", win_id, next);
+        self.column_widths.insert(win_id.clone(), next);
+
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+
+    /// monocle 下左右轮换整个标签的窗口，tabbed 容器内左右轮换、stacked 容器内上下轮换，
+    /// scroller 左右移到相邻的列；已经到头或不在这类容器里时返回 None，交给普通的方向查找
+    fn cycle_hidden_sibling(&self, win_id: &ObjectId, dir: Direction) -> Option<ObjectId> {
+        let w = self.windows.iter().find(|w| &w.id == win_id)?;
+        let tree_key = (w.output.clone()?, w.tags);
+        let root = self.layout_roots.get(&tree_key)?;
+
+        let (axis_matches, siblings) = match self.tag_layout(&tree_key).mode {
+            LayoutMode::Monocle | LayoutMode::Scroller => (
+                matches!(dir, Direction::Left | Direction::Right),
+                root.leaves().iter().map(|w| w.id.clone()).collect(),
+            ),
+            LayoutMode::Tree => {
+                let (mode, ids) = root.tab_group(win_id)?;
+                let axis_matches = match mode {
+                    ContainerMode::Tabbed => matches!(dir, Direction::Left | Direction::Right),
+                    ContainerMode::Stacked => matches!(dir, Direction::Up | Direction::Down),
+                    ContainerMode::Split => false,
+                };
+                (axis_matches, ids)
+            }
+            LayoutMode::MasterStack => return None,
+        };
+        if !axis_matches {
+            return None;
+        }
+
+        let idx = siblings.iter().position(|id| id == win_id)?;
+        let next = match dir {
+            Direction::Left | Direction::Up => idx.checked_sub(1)?,
+            Direction::Right | Direction::Down => idx + 1,
+        };
+        siblings.get(next).cloned()
+    }
+
+    /// 主从布局下的尺寸调整：目标在主区时增长意味着主区变大，在栈区时则相反
+    fn resize_master(
+        &mut self,
+        tree_key: &(String, u32),
+        win_id: &ObjectId,
+        area: Geometry,
+        axis: ResizeAxis,
+        delta: i32,
+    ) {
+        let mut layout = self.tag_layout(tree_key);
+        let total_px = match (layout.master_location, axis) {
+            (MasterLocation::Left | MasterLocation::Right, ResizeAxis::Horizontal) => area.w,
+            (MasterLocation::Top | MasterLocation::Bottom, ResizeAxis::Vertical) => area.h,
+            // 与主区分割线平行的方向没有可调的东西
+            _ => return,
+        };
+        if total_px <= 0 {
+            return;
+        }
+
+        let is_master = self.layout_roots.get(tree_key).is_some_and(|root| {
+            root.leaves()
+                .iter()
+                .position(|w| &w.id == win_id)
+                .is_some_and(|i| i < layout.master_count as usize)
+        });
+        let delta_ratio = delta as f32 / total_px as f32;
+        let new_ratio = if is_master {
+            layout.master_ratio + delta_ratio
+        } else {
+            layout.master_ratio - delta_ratio
+        };
+        layout.master_ratio = new_ratio.clamp(0.05, 0.95);
+        self.tag_layouts.insert(tree_key.clone(), layout);
+    }
+
     /// 智能动态流转：增加方向感知和边缘焦点锁定
     fn cycle_tag(&mut self, delta: i32, dir: Direction) {
         let out_id = match &self.focused_output {
@@ -2232,14 +3166,16 @@ impl AppState {
         let current_out_name = &current_w_data.output;
 
         let cur_geo = self.last_geometry.get(current_id)?;
+        let hidden = self.hidden_tiled_windows();
 
-        // 地理围栏：只在同一个显示器内寻找邻居
+        // 地理围栏：只在同一个显示器内寻找邻居 (跳过被 monocle/tabbed 藏起来的窗口)
         self.windows
             .iter()
             .filter(|w| {
                 &w.id != current_id
                     && (w.tags & self.focused_tags) != 0
                     && &w.output == current_out_name
+                    && !hidden.contains(&w.id)
             })
             .filter_map(|w| {
                 let g = self.last_geometry.get(&w.id)?;
@@ -2379,6 +3315,57 @@ impl AppState {
             }
         }
     }
+    /// 切换 sticky：平铺窗口先变成悬浮窗，取消时留在当前标签
+    fn toggle_sticky(&mut self, win_id: &ObjectId) {
+        let (is_sticky, is_floating) = match self.windows.iter().find(|w| &w.id == win_id) {
+            Some(w) => (w.is_sticky, w.is_floating),
+            None => return,
+        };
+        if !is_sticky && !is_floating {
+            self.make_window_floating(win_id, 0, 0);
+        }
+        if let Some(w) = self.windows.iter_mut().find(|w| &w.id == win_id) {
+            w.is_sticky = !is_sticky;
+            info!("-> [Sticky] Window {:?} sticky: {}", win_id, w.is_sticky);
+        }
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+
+    /// sticky 窗口换到所在显示器当前的标签；配置了 sticky_all_outputs 时还会跟着焦点显示器走，
+    /// 在新显示器上保持相对位置
+    pub fn follow_sticky_windows(&mut self) {
+        let all_outputs = self
+            .config
+            .window
+            .as_ref()
+            .and_then(|c| c.sticky_all_outputs.as_deref())
+            .is_some_and(|s| s.to_lowercase() == "true");
+        let focused_out = self.focused_output.clone();
+
+        for w in self.windows.iter_mut() {
+            if !w.is_sticky || !w.is_floating || w.is_minimized {
+                continue;
+            }
+            if let Some(target) = focused_out.as_ref().filter(|_| all_outputs) {
+                if w.output.as_ref() != Some(target) {
+                    let old_area = w.output.as_ref().and_then(|o| self.outputs.get(o));
+                    if let (Some(old), Some(new)) = (old_area, self.outputs.get(target)) {
+                        let (old, new) = (old.usable_area, new.usable_area);
+                        let geo = w.float_geo;
+                        w.float_geo.x = new.x + (geo.x - old.x).clamp(0, (new.w - geo.w).max(0));
+                        w.float_geo.y = new.y + (geo.y - old.y).clamp(0, (new.h - geo.h).max(0));
+                    }
+                    w.output = Some(target.clone());
+                }
+            }
+            if let Some(out_data) = w.output.as_ref().and_then(|o| self.outputs.get(o)) {
+                w.tags = out_data.tags;
+            }
+        }
+    }
+
     /// 辅助：将一个现有的平铺窗口强制转换为悬浮窗口（用于弹窗启发式算法）
     pub fn make_window_floating(
         &mut self,
@@ -2460,14 +3447,26 @@ impl AppState {
             None => return,
         };
 
+        // rrwm 重启后重新出现的窗口按状态文件还原，不再套用规则
+        if self.restore_window(win_id) {
+            return;
+        }
+
         // 只有在窗口诞生的前 1000 毫秒内，才允许规则引擎评估它的状态。
         if created_at.elapsed().as_millis() > 1000 {
             return;
         }
+        self.session_dirty = true;
+
+        // 刚启动的 scratchpad 的窗口直接按 scratchpad 的设置悬浮显示
+        if self.claim_scratchpad(win_id) {
+            return;
+        }
 
         // 2. 规则匹配 (得分制优先级机制)
         let mut best_score = 0;
         let (mut r_float, mut r_fs, mut r_w, mut r_h) = (None, None, None, None);
+        let (mut r_sticky, mut r_swallow) = (None, None);
 
         if let Some(rules) = self
             .config
@@ -2506,6 +3505,8 @@ impl AppState {
                         best_score = current_score;
                         r_float = rule.floating.clone();
                         r_fs = rule.fullscreen.clone();
+                        r_sticky = rule.sticky.clone();
+                        r_swallow = rule.swallow.clone();
                         r_w = rule.width.clone();
                         r_h = rule.height.clone();
                     }
@@ -2513,8 +3514,12 @@ impl AppState {
             }
         }
 
-        // 3. 状态决策
+        // 3. 状态决策 (sticky 窗口总是悬浮)
+        let should_sticky = r_sticky
+            .as_deref()
+            .is_some_and(|s| s.to_lowercase() == "true");
         let should_float = match r_float.as_deref() {
+            _ if should_sticky => true,
             Some(s) if s.to_lowercase() == "true" => true,
             Some(s) if s.to_lowercase() == "false" => false,
             _ => is_fixed || has_parent, // 系统启发式推断
@@ -2523,11 +3528,11 @@ impl AppState {
             .as_deref()
             .map(|s| s.to_lowercase() == "true")
             .unwrap_or(false);
+        let swallow = r_swallow.as_deref().map(|s| s.to_lowercase());
 
         // 4. 计算比例
         let tree_key = (out_id.clone(), tags);
         let mut custom_ratio = None;
-        let mut split = crate::wm::layout::SplitType::Vertical;
 
         let target_id = self
             .tag_focus_history
@@ -2535,7 +3540,8 @@ impl AppState {
             .filter(|&id| *id != *win_id)
             .cloned();
         let (ref_w, ref_h) = target_id
-            .and_then(|tid| self.last_geometry.get(&tid).map(|g| (g.w, g.h)))
+            .as_ref()
+            .and_then(|tid| self.last_geometry.get(tid).map(|g| (g.w, g.h)))
             .unwrap_or_else(|| {
                 self.outputs
                     .get(&out_id)
@@ -2543,9 +3549,15 @@ impl AppState {
                     .unwrap_or((1920, 1080))
             });
 
-        if ref_w < ref_h {
-            split = crate::wm::layout::SplitType::Horizontal;
-        }
+        // 切分方向由标签的切分策略决定 (默认沿目标较长的一边)
+        let split = target_id
+            .as_ref()
+            .map(|tid| self.policy_split(&tree_key, tid).0)
+            .unwrap_or(if ref_w < ref_h {
+                crate::wm::layout::SplitType::Horizontal
+            } else {
+                crate::wm::layout::SplitType::Vertical
+            });
 
         if split == crate::wm::layout::SplitType::Vertical {
             if let Some(w_str) = r_w.as_deref() {
@@ -2565,6 +3577,8 @@ impl AppState {
         }
         if let Some(w) = self.windows.iter_mut().find(|w| &w.id == win_id) {
             w.is_fullscreen = should_fs;
+            w.is_sticky = should_sticky;
+            w.is_terminal = swallow.as_deref() == Some("true");
         }
 
         if should_float {
@@ -2618,29 +3632,12 @@ impl AppState {
                     .find(|w| &w.id == win_id)
                     .cloned()
                     .unwrap();
-                if !self.layout_roots.contains_key(&tree_key) {
-                    self.layout_roots.insert(
-                        tree_key.clone(),
-                        crate::wm::layout::LayoutNode::Window(w_data),
-                    );
-                } else if let Some(mut root) = self.layout_roots.remove(&tree_key) {
-                    let tid = self
-                        .tag_focus_history
-                        .get(&tree_key)
-                        .cloned()
-                        .unwrap_or_else(|| win_id.clone());
-                    if !root.insert_at(&tid, w_data.clone(), split, custom_ratio) {
-                        let new_root = crate::wm::layout::LayoutNode::Container {
-                            split_type: crate::wm::layout::SplitType::Vertical,
-                            ratio: custom_ratio.unwrap_or(0.5),
-                            left_child: Box::new(root),
-                            right_child: Box::new(crate::wm::layout::LayoutNode::Window(w_data)),
-                        };
-                        self.layout_roots.insert(tree_key.clone(), new_root);
-                    } else {
-                        self.layout_roots.insert(tree_key.clone(), root);
-                    }
-                }
+                let tid = self
+                    .tag_focus_history
+                    .get(&tree_key)
+                    .cloned()
+                    .unwrap_or_else(|| win_id.clone());
+                self.insert_tiled(&tree_key, &tid, w_data, custom_ratio);
 
                 // 平铺窗强行夺取焦点
                 self.focused_window = Some(win_id.clone());
@@ -2659,6 +3656,12 @@ impl AppState {
                 }
             }
         }
+        // 从终端里启动的窗口顶替终端的位置，否则看看当前标签有没有还空着的布局预设
+        let swallowed =
+            !should_float && swallow.as_deref() != Some("false") && self.swallow_parent(win_id);
+        if !swallowed {
+            self.fill_preset_slot(win_id);
+        }
         if let Some(wm) = &self.river_wm {
             info!("-> MANAGE_DIRTY TRIGGERED BY:apply_window_rules");
             wm.manage_dirty();
@@ -0,0 +1,335 @@
+use crate::config::HookEntry;
+use crate::wm::actions::Action;
+use crate::wm::ipc::{IpcRequest, WindowInfo};
+use crate::wm::AppState;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::process::Command;
+use tracing::{error, info, warn};
+
+/// 可订阅的事件名，subscribe 请求的 args 只能从这里挑
+pub const EVENT_NAMES: &[&str] = &[
+    "window_opened",
+    "window_closed",
+    "focus_changed",
+    "title_changed",
+    "tags_changed",
+    "output_added",
+    "output_removed",
+    "mode_changed",
+    "fullscreen_changed",
+    "config_reloaded",
+];
+
+/// 推送给订阅者的事件，每个事件序列化为一行 JSON
+/// 例如：{"event":"focus_changed","window":{"id":12,"app_id":"kitty",...}}
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum IpcEvent {
+    WindowOpened { window: WindowInfo },
+    WindowClosed { window: WindowInfo },
+    FocusChanged { window: Option<WindowInfo> },
+    TitleChanged { window: WindowInfo },
+    TagsChanged { output: String, tags: u32 },
+    OutputAdded { output: String },
+    OutputRemoved { output: String },
+    ModeChanged { mode: String },
+    FullscreenChanged { window: WindowInfo },
+    ConfigReloaded,
+}
+
+impl IpcEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IpcEvent::WindowOpened { .. } => "window_opened",
+            IpcEvent::WindowClosed { .. } => "window_closed",
+            IpcEvent::FocusChanged { .. } => "focus_changed",
+            IpcEvent::TitleChanged { .. } => "title_changed",
+            IpcEvent::TagsChanged { .. } => "tags_changed",
+            IpcEvent::OutputAdded { .. } => "output_added",
+            IpcEvent::OutputRemoved { .. } => "output_removed",
+            IpcEvent::ModeChanged { .. } => "mode_changed",
+            IpcEvent::FullscreenChanged { .. } => "fullscreen_changed",
+            IpcEvent::ConfigReloaded => "config_reloaded",
+        }
+    }
+
+    /// 传给 hooks 命令的环境变量，window 类事件附带窗口信息
+    fn hook_env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![("RRWM_EVENT", self.name().to_string())];
+        match self {
+            IpcEvent::WindowOpened { window }
+            | IpcEvent::WindowClosed { window }
+            | IpcEvent::TitleChanged { window }
+            | IpcEvent::FullscreenChanged { window }
+            | IpcEvent::FocusChanged {
+                window: Some(window),
+            } => {
+                env.push(("RRWM_WINDOW_ID", window.id.to_string()));
+                env.push(("RRWM_APP_ID", window.app_id.clone().unwrap_or_default()));
+                env.push(("RRWM_TITLE", window.title.clone().unwrap_or_default()));
+                env.push(("RRWM_TAGS", window.tags.to_string()));
+                env.push(("RRWM_OUTPUT", window.output.clone().unwrap_or_default()));
+                env.push((
+                    "RRWM_FULLSCREEN",
+                    if window.is_fullscreen { "1" } else { "0" }.to_string(),
+                ));
+            }
+            IpcEvent::TagsChanged { output, tags } => {
+                env.push(("RRWM_OUTPUT", output.clone()));
+                env.push(("RRWM_TAGS", tags.to_string()));
+            }
+            IpcEvent::OutputAdded { output } | IpcEvent::OutputRemoved { output } => {
+                env.push(("RRWM_OUTPUT", output.clone()));
+            }
+            IpcEvent::ModeChanged { mode } => env.push(("RRWM_MODE", mode.clone())),
+            IpcEvent::FocusChanged { window: None } | IpcEvent::ConfigReloaded => {}
+        }
+        env
+    }
+}
+
+/// 一个保持连接的订阅者，events 为空表示订阅全部事件
+pub struct EventSubscriber {
+    pub stream: UnixStream,
+    pub events: Vec<String>,
+}
+
+/// 上一次推送时的状态快照，事件通过前后两次快照的差异得出
+#[derive(Default, Clone)]
+pub struct EventSnapshot {
+    pub windows: Vec<WindowInfo>,
+    pub focused: Option<u32>,
+    pub output_tags: HashMap<String, u32>,
+    pub resize_mode: bool,
+}
+
+/// 如果请求是 subscribe，返回要订阅的事件列表 (或错误信息)；否则返回 None
+pub fn parse_subscribe(raw: &str) -> Option<Result<Vec<String>, String>> {
+    let req = serde_json::from_str::<IpcRequest>(raw).ok()?;
+    if req.action != "subscribe" {
+        return None;
+    }
+
+    let events = req.args.unwrap_or_default();
+    if let Some(bad) = events.iter().find(|e| !EVENT_NAMES.contains(&e.as_str())) {
+        return Some(Err(format!(
+            "Unknown event name {} (expected one of: {})",
+            bad,
+            EVENT_NAMES.join(", ")
+        )));
+    }
+    Some(Ok(events))
+}
+
+impl AppState {
+    /// 登记新的订阅者 (快照一直保持最新，所以不会一连上就收到一堆“历史”事件)
+    pub fn add_event_subscriber(&mut self, stream: UnixStream, events: Vec<String>) {
+        info!("-> [IPC] New event subscriber: {:?}", events);
+        // 推送在 WM 线程上进行：不读数据的订阅者写满缓冲区后直接写失败被移除，而不是卡住 WM
+        if let Err(e) = stream.set_nonblocking(true) {
+            warn!("-> [IPC] Cannot make event subscriber non-blocking: {}", e);
+            return;
+        }
+        self.event_subscribers
+            .push(EventSubscriber { stream, events });
+    }
+
+    /// 采集当前状态：只统计已经拿到 AppId 的窗口
+    fn take_event_snapshot(&self) -> EventSnapshot {
+        EventSnapshot {
+            windows: self
+                .windows
+                .iter()
+                .filter(|w| w.app_id.is_some())
+                .map(|w| self.window_info(w))
+                .collect(),
+            focused: self.focused_window.as_ref().map(|id| id.protocol_id()),
+            output_tags: self
+                .outputs
+                .iter()
+                .map(|(name, out)| (name.clone(), out.tags))
+                .collect(),
+            resize_mode: self.is_resize_mode,
+        }
+    }
+
+    /// 对比快照生成事件，并推送给订阅了对应事件的客户端
+    pub fn emit_events(&mut self) {
+        let new = self.take_event_snapshot();
+        let has_hooks = self.config.hooks.as_ref().is_some_and(|h| !h.is_empty());
+        if self.event_subscribers.is_empty() && !has_hooks {
+            // 没人关心事件时也要刷新快照，避免之后订阅/重载时补发过期的变化
+            self.last_event_snapshot = new;
+            return;
+        }
+
+        let old = std::mem::take(&mut self.last_event_snapshot);
+        let mut events = Vec::new();
+
+        // 1. 显示器增减与标签切换 (按名字排序，保证输出顺序稳定)
+        let mut names: Vec<&String> = old
+            .output_tags
+            .keys()
+            .chain(new.output_tags.keys())
+            .collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            match (old.output_tags.get(name), new.output_tags.get(name)) {
+                (None, Some(tags)) => {
+                    events.push(IpcEvent::OutputAdded {
+                        output: name.clone(),
+                    });
+                    events.push(IpcEvent::TagsChanged {
+                        output: name.clone(),
+                        tags: *tags,
+                    });
+                }
+                (Some(_), None) => events.push(IpcEvent::OutputRemoved {
+                    output: name.clone(),
+                }),
+                (Some(old_tags), Some(tags)) if old_tags != tags => {
+                    events.push(IpcEvent::TagsChanged {
+                        output: name.clone(),
+                        tags: *tags,
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        // 2. 窗口关闭：旧快照里有、新快照里没有
+        for w in &old.windows {
+            if !new.windows.iter().any(|n| n.id == w.id) {
+                events.push(IpcEvent::WindowClosed { window: w.clone() });
+            }
+        }
+
+        // 3. 窗口打开、标题与全屏状态变化
+        for w in &new.windows {
+            match old.windows.iter().find(|o| o.id == w.id) {
+                None => events.push(IpcEvent::WindowOpened { window: w.clone() }),
+                Some(o) => {
+                    if o.title != w.title {
+                        events.push(IpcEvent::TitleChanged { window: w.clone() });
+                    }
+                    if o.is_fullscreen != w.is_fullscreen {
+                        events.push(IpcEvent::FullscreenChanged { window: w.clone() });
+                    }
+                }
+            }
+        }
+
+        // 4. 焦点变化 (失焦时 window 为 null)
+        if old.focused != new.focused {
+            events.push(IpcEvent::FocusChanged {
+                window: new
+                    .windows
+                    .iter()
+                    .find(|w| Some(w.id) == new.focused)
+                    .cloned(),
+            });
+        }
+
+        // 5. 绑定模式切换
+        if old.resize_mode != new.resize_mode {
+            let mode = if new.resize_mode { "resize" } else { "normal" };
+            events.push(IpcEvent::ModeChanged {
+                mode: mode.to_string(),
+            });
+        }
+
+        self.last_event_snapshot = new;
+
+        for event in &events {
+            self.publish_event(event);
+        }
+    }
+
+    /// 发布单个事件：推送给订阅者 (写入失败或缓冲区已满的会被移除)，并执行对应的 hooks
+    pub fn publish_event(&mut self, event: &IpcEvent) {
+        self.run_hooks(event);
+
+        let mut packet = serde_json::to_string(event).unwrap_or_default();
+        packet.push('\n');
+
+        let name = event.name();
+        self.event_subscribers.retain_mut(|sub| {
+            if !sub.events.is_empty() && !sub.events.iter().any(|e| e == name) {
+                return true;
+            }
+            sub.stream.write_all(packet.as_bytes()).is_ok()
+        });
+    }
+
+    /// 执行 rrwm.toml [hooks] 中为该事件配置的 spawn/shell 命令
+    fn run_hooks(&self, event: &IpcEvent) {
+        let entry = match self
+            .config
+            .hooks
+            .as_ref()
+            .and_then(|hooks| hooks.get(event.name()))
+        {
+            Some(e) => e,
+            None => return,
+        };
+        let configs = match entry {
+            HookEntry::Action(cfg) => std::slice::from_ref(cfg),
+            HookEntry::List(list) => list.as_slice(),
+        };
+
+        let env = event.hook_env();
+        for cfg in configs {
+            let mut command =
+                match Action::parse(&cfg.action, &cfg.args, &cfg.cmd, &cfg.unit, "hook") {
+                    Ok(Action::Spawn(cmd_list)) if !cmd_list.is_empty() => {
+                        let mut c = Command::new(&cmd_list[0]);
+                        c.args(&cmd_list[1..]);
+                        c
+                    }
+                    Ok(Action::Shell(cmd_str)) if !cmd_str.is_empty() => {
+                        let mut c = Command::new("sh");
+                        c.arg("-c").arg(cmd_str);
+                        c
+                    }
+                    _ => {
+                        warn!(
+                            "-> [Hook] {}: expected a non-empty spawn/shell action, got {:?}",
+                            event.name(),
+                            cfg.action
+                        );
+                        continue;
+                    }
+                };
+
+            info!("-> [Hook] {} -> {:?}", event.name(), command);
+            match command.envs(env.iter().map(|(k, v)| (*k, v))).spawn() {
+                // 在后台线程里等它退出，避免每次触发都留下一个僵尸进程
+                Ok(mut child) => {
+                    std::thread::spawn(move || {
+                        let _ = child.wait();
+                    });
+                }
+                Err(e) => error!("-> Hook execution failed: {}", e),
+            }
+        }
+    }
+
+    /// 加载 (或重载) 配置后检查 [hooks]：写错的事件名永远不会触发，提醒一下
+    pub fn check_hook_names(&self) {
+        if let Some(hooks) = &self.config.hooks {
+            for name in hooks.keys() {
+                if !EVENT_NAMES.contains(&name.as_str()) {
+                    warn!(
+                        "-> [Hook] Unknown event name {} in [hooks] (expected one of: {})",
+                        name,
+                        EVENT_NAMES.join(", ")
+                    );
+                }
+            }
+        }
+    }
+}
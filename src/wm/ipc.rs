@@ -0,0 +1,645 @@
+use crate::protocol::river_wm::river_window_v1::RiverWindowV1;
+use crate::wm::actions::Action;
+use crate::wm::layout::{
+    split_constrained, ContainerMode, Geometry, LayoutNode, SizeHints, SplitType, TagLayout,
+};
+use crate::wm::status::StatusFormat;
+use crate::wm::AppState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::time::Duration;
+use tracing::{info, warn};
+use wayland_backend::client::ObjectId;
+use wayland_client::{Proxy, QueueHandle};
+
+/// 指令 Socket 收到的结构化请求，字段与 rrwm.toml 里的动作配置一一对应
+/// 例如：{ "action": "focus", "args": ["3"] }
+#[derive(Deserialize, Debug, Clone)]
+pub struct IpcRequest {
+    pub action: String,
+    pub args: Option<Vec<String>>,
+    pub cmd: Option<String>,
+    pub unit: Option<String>,
+    pub criteria: Option<IpcCriteria>,
+    // 先聚焦这个显示器再执行动作，例如每个显示器状态栏上的标签按钮
+    pub output: Option<String>,
+    pub format: Option<String>, // 仅 subscribe_status 使用：waybar / i3bar / eww / plain
+    pub tag: Option<u32>,       // 仅 subscribe_status 使用：只推送这一个标签 (从 1 开始)
+}
+
+/// 按条件挑选目标窗口，所有写了的条件都必须满足
+/// 例如：{ "action": "move", "args": ["9"], "criteria": { "app_id": "mpv" } }
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct IpcCriteria {
+    pub id: Option<u32>,
+    pub app_id: Option<String>, // 子串匹配，不区分大小写 (与 window.rule 一致)
+    pub app_id_regex: Option<String>, // 正则匹配
+    pub title: Option<String>,  // 正则匹配
+    pub tag: Option<u32>,       // 标签序号，从 1 开始
+    pub output: Option<String>,
+    pub floating: Option<bool>,
+    pub mark: Option<String>,
+}
+
+/// 窗口快照：供 get_windows 等查询序列化使用
+#[derive(Serialize, Debug, Clone)]
+pub struct WindowInfo {
+    pub id: u32,
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+    pub output: Option<String>,
+    pub tags: u32,
+    pub is_floating: bool,
+    pub is_fullscreen: bool,
+    pub is_minimized: bool,
+    pub is_sticky: bool,
+    pub mark: Option<String>,
+    pub focused: bool,
+}
+
+/// 布局树节点快照：供 get_tree 序列化使用，geometry 与 calculate_layout 的结果一致
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TreeNodeInfo {
+    Window {
+        id: u32,
+        app_id: Option<String>,
+        title: Option<String>,
+        geometry: Geometry,
+        hidden: bool, // 被 monocle 或 tabbed/stacked 容器藏起来
+    },
+    Container {
+        split_type: SplitType,
+        ratio: f32,
+        mode: ContainerMode,
+        geometry: Geometry,
+        children: Vec<TreeNodeInfo>,
+    },
+}
+
+/// 某个标签下的平铺树与悬浮窗口
+#[derive(Serialize, Debug, Clone)]
+pub struct TagTreeInfo {
+    pub tag: u32,
+    pub visible: bool,
+    pub layout: TagLayout,
+    pub root: Option<TreeNodeInfo>,
+    pub floating: Vec<WindowInfo>,
+}
+
+/// 某个显示器下的全部标签，以及被最小化收起的窗口
+#[derive(Serialize, Debug, Clone)]
+pub struct OutputTreeInfo {
+    pub name: String,
+    pub focused: bool,
+    pub usable_area: Geometry,
+    pub tags: Vec<TagTreeInfo>,
+    pub minimized: Vec<WindowInfo>,
+}
+
+/// 显示模式，refresh 单位为 Hz
+#[derive(Serialize, Debug, Clone)]
+pub struct ModeDetail {
+    pub width: i32,
+    pub height: i32,
+    pub refresh: f64,
+    pub current: bool,
+}
+
+/// 显示器快照：供 get_outputs 序列化使用
+#[derive(Serialize, Debug, Clone)]
+pub struct OutputInfo {
+    pub name: String,
+    pub focused: bool,
+    pub modes: Vec<ModeDetail>,
+    pub current_mode: Option<ModeDetail>,
+    pub full_area: Option<Geometry>,
+    pub usable_area: Option<Geometry>,
+    pub tags: Option<u32>,
+}
+
+/// 返回给客户端的统一回执
+#[derive(Serialize, Debug, Clone)]
+pub struct IpcResponse {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl IpcResponse {
+    pub fn ok(data: Option<serde_json::Value>) -> Self {
+        IpcResponse {
+            success: true,
+            error: None,
+            data,
+        }
+    }
+
+    pub fn err(msg: impl Into<String>) -> Self {
+        IpcResponse {
+            success: false,
+            error: Some(msg.into()),
+            data: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_string(self).unwrap_or_default();
+        json.push('\n');
+        json
+    }
+}
+
+/// 状态栏订阅参数：显示器 (不填则跟随焦点显示器)、单个标签 (不填则整行) 与输出格式
+struct StatusSubscription {
+    output: Option<String>,
+    tag: Option<u32>,
+    format: StatusFormat,
+}
+
+/// 如果请求是 subscribe_status，返回订阅参数 (或错误信息)；否则返回 None
+/// 例如：{ "action": "subscribe_status", "args": ["HDMI-A-1"], "format": "i3bar", "tag": 3 }
+fn parse_status_subscribe(raw: &str) -> Option<Result<StatusSubscription, String>> {
+    let req = serde_json::from_str::<IpcRequest>(raw).ok()?;
+    if req.action != "subscribe_status" {
+        return None;
+    }
+    if let Some(t) = req.tag {
+        if !(1..=32).contains(&t) {
+            return Some(Err(format!("Tag {} out of range (expected 1-32)", t)));
+        }
+    }
+    let format = match req.format.as_deref() {
+        Some(name) => match StatusFormat::parse(name) {
+            Ok(f) => f,
+            Err(e) => return Some(Err(e)),
+        },
+        None => StatusFormat::Waybar,
+    };
+    Some(Ok(StatusSubscription {
+        output: req.args.and_then(|args| args.into_iter().next()),
+        tag: req.tag,
+        format,
+    }))
+}
+
+/// 指令连接读写的最长等待时间：连上却不发数据 (或不读回执) 的客户端不能卡住 WM
+const CLIENT_TIMEOUT: Duration = Duration::from_millis(200);
+
+impl AppState {
+    /// 核心：处理指令 Socket 连接 (如 rrwm --appid / 脚本发送的 JSON 动作 / 事件订阅)
+    pub fn handle_command_connections(&mut self, qh: &QueueHandle<Self>) {
+        let mut streams = Vec::new();
+        if let Some(ref listener) = self.cmd_listener {
+            // accept() 是非阻塞的
+            while let Ok((stream, _)) = listener.accept() {
+                streams.push(stream);
+            }
+        }
+
+        for mut stream in streams {
+            // accept() 出来的连接不会继承监听端的非阻塞标志，读写都加上超时
+            let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+            let _ = stream.set_write_timeout(Some(CLIENT_TIMEOUT));
+
+            // 1. 读取指令
+            let mut buf = [0; 4096];
+            // 尝试读取，如果客户端连接了但没发数据，这里可能会 WouldBlock。
+            // 但对于本地 CLI 工具，通常数据是随连接瞬间到达的。
+            // 为了鲁棒性，我们简单尝试读取，读不到就忽略。
+            if let Ok(n) = stream.read(&mut buf) {
+                let command = String::from_utf8_lossy(&buf[..n]).trim().to_string();
+
+                // 订阅请求：回执之后保留连接，事件会逐行推送过去
+                if let Some(subscription) = crate::wm::events::parse_subscribe(&command) {
+                    match subscription {
+                        Ok(events) => {
+                            if stream
+                                .write_all(IpcResponse::ok(None).to_json().as_bytes())
+                                .is_ok()
+                            {
+                                self.add_event_subscriber(stream, events);
+                            }
+                        }
+                        Err(e) => {
+                            let _ = stream.write_all(IpcResponse::err(e).to_json().as_bytes());
+                        }
+                    }
+                    continue;
+                }
+
+                // 状态栏订阅：回执之后持续推送指定显示器、指定格式的状态
+                if let Some(subscription) = parse_status_subscribe(&command) {
+                    match subscription {
+                        Ok(sub) => {
+                            if stream
+                                .write_all(IpcResponse::ok(None).to_json().as_bytes())
+                                .is_ok()
+                            {
+                                info!(
+                                    "-> [IPC] New status subscriber for output {:?}, tag {:?} ({:?})",
+                                    sub.output, sub.tag, sub.format
+                                );
+                                self.add_status_client(stream, sub.output, sub.tag, sub.format);
+                            }
+                        }
+                        Err(e) => {
+                            let _ = stream.write_all(IpcResponse::err(e).to_json().as_bytes());
+                        }
+                    }
+                    continue;
+                }
+
+                // 2. 路由指令：旧的纯文本指令保持原样输出，其余一律按 JSON 请求处理
+                let response = match command.as_str() {
+                    "ls_clients" => self.get_app_ids_report(),
+                    _ => self.handle_json_request(&command, qh).to_json(),
+                };
+
+                // 3. 写回响应并关闭连接
+                let _ = stream.write_all(response.as_bytes());
+            }
+        }
+    }
+
+    /// 解析 JSON 请求：先匹配查询类指令，其余交给 Action::parse 与 perform_action
+    fn handle_json_request(&mut self, raw: &str, qh: &QueueHandle<Self>) -> IpcResponse {
+        let req = match serde_json::from_str::<IpcRequest>(raw) {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("-> [IPC] Invalid request {:?}: {}", raw, e);
+                return IpcResponse::err(format!("Invalid request: {}", e));
+            }
+        };
+
+        if let Some(data) = self.run_query(&req) {
+            return IpcResponse::ok(Some(data));
+        }
+
+        if let Some(out_name) = &req.output {
+            if let Err(e) = self.focus_output_named(out_name) {
+                return IpcResponse::err(e);
+            }
+        }
+
+        if let Some(criteria) = &req.criteria {
+            return self.run_for_criteria(&req, criteria);
+        }
+
+        match Action::parse(&req.action, &req.args, &req.cmd, &req.unit, "ipc") {
+            Ok(action) => {
+                info!("-> [IPC] Execute action: {:?}", action);
+                // 布局预设失败时 (名字不存在、没有可保存的窗口等) 要把原因回给客户端
+                let result = match &action {
+                    Action::SaveLayout(name) => self.save_layout(name),
+                    Action::LoadLayout(name) => {
+                        self.session_dirty = true;
+                        self.load_layout(name)
+                    }
+                    _ => {
+                        self.run_actions(vec![action], qh);
+                        Ok(())
+                    }
+                };
+                match result {
+                    Ok(()) => IpcResponse::ok(None),
+                    Err(e) => {
+                        warn!("-> [IPC] {}", e);
+                        IpcResponse::err(e)
+                    }
+                }
+            }
+            Err(e) => IpcResponse::err(e),
+        }
+    }
+
+    /// 按名字聚焦显示器，焦点落回该显示器当前标签上次聚焦的窗口
+    fn focus_output_named(&mut self, out_name: &str) -> Result<(), String> {
+        let tags = match self.outputs.get(out_name) {
+            Some(o) => o.tags,
+            None => return Err(format!("Unknown output {}", out_name)),
+        };
+        if self.focused_output.as_deref() == Some(out_name) {
+            return Ok(());
+        }
+        info!("-> [IPC] Focusing output {} before the action", out_name);
+        let key = (out_name.to_string(), tags);
+        self.focused_window = self
+            .tag_focus_history
+            .get(&key)
+            .filter(|id| {
+                self.windows
+                    .iter()
+                    .any(|w| &w.id == *id && w.output.as_deref() == Some(out_name))
+            })
+            .cloned();
+        self.focused_output = Some(key.0);
+        self.focused_tags = tags;
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+        Ok(())
+    }
+
+    /// 对所有满足条件的窗口执行同一个动作，回执里带上命中的窗口 id
+    fn run_for_criteria(&mut self, req: &IpcRequest, criteria: &IpcCriteria) -> IpcResponse {
+        let action = match Action::parse(&req.action, &req.args, &req.cmd, &req.unit, "ipc") {
+            Ok(a) => a,
+            Err(e) => return IpcResponse::err(e),
+        };
+        if !action.targets_window() {
+            return IpcResponse::err(format!(
+                "Action {} does not target a window and cannot be used with criteria",
+                req.action
+            ));
+        }
+
+        // 先编译正则，写错了直接报错而不是静默匹配不到
+        let compile = |pattern: &Option<String>| {
+            pattern
+                .as_deref()
+                .map(regex_lite::Regex::new)
+                .transpose()
+                .map_err(|e| format!("Invalid regex: {}", e))
+        };
+        let (app_id_re, title_re) =
+            match (compile(&criteria.app_id_regex), compile(&criteria.title)) {
+                (Ok(a), Ok(t)) => (a, t),
+                (Err(e), _) | (_, Err(e)) => return IpcResponse::err(e),
+            };
+
+        let targets: Vec<_> = self
+            .windows
+            .iter()
+            .filter(|w| {
+                let app_id = w.app_id.as_deref().unwrap_or("");
+                let title = w.title.as_deref().unwrap_or("");
+                criteria.id.is_none_or(|id| w.id.protocol_id() == id)
+                    && criteria
+                        .app_id
+                        .as_ref()
+                        .is_none_or(|a| app_id.to_lowercase().contains(&a.to_lowercase()))
+                    && app_id_re.as_ref().is_none_or(|re| re.is_match(app_id))
+                    && title_re.as_ref().is_none_or(|re| re.is_match(title))
+                    && criteria
+                        .tag
+                        .is_none_or(|t| (1..=32).contains(&t) && (w.tags & (1 << (t - 1))) != 0)
+                    && criteria
+                        .output
+                        .as_ref()
+                        .is_none_or(|o| w.output.as_ref() == Some(o))
+                    && criteria.floating.is_none_or(|f| w.is_floating == f)
+                    && criteria
+                        .mark
+                        .as_ref()
+                        .is_none_or(|m| w.mark.as_ref() == Some(m))
+            })
+            .map(|w| w.id.clone())
+            .collect();
+
+        info!(
+            "-> [IPC] Execute action {:?} on {} matching window(s)",
+            action,
+            targets.len()
+        );
+        for id in &targets {
+            self.perform_action_on(id, action.clone());
+        }
+
+        let ids: Vec<u32> = targets.iter().map(|id| id.protocol_id()).collect();
+        IpcResponse::ok(Some(serde_json::json!({ "matched": ids })))
+    }
+
+    /// 只读查询：命中则返回数据，否则交给动作系统
+    fn run_query(&self, req: &IpcRequest) -> Option<serde_json::Value> {
+        match req.action.as_str() {
+            "ls_clients" => Some(serde_json::Value::String(self.get_app_ids_report())),
+            "get_windows" => {
+                let list: Vec<WindowInfo> =
+                    self.windows.iter().map(|w| self.window_info(w)).collect();
+                serde_json::to_value(list).ok()
+            }
+            "get_tree" => serde_json::to_value(self.get_tree()).ok(),
+            "list_layouts" => serde_json::to_value(crate::wm::presets::list_presets()).ok(),
+            "get_outputs" => serde_json::to_value(self.get_outputs()).ok(),
+            _ => None,
+        }
+    }
+
+    /// 把内部 WindowData 转成对外的窗口快照
+    pub fn window_info(&self, w: &crate::wm::WindowData) -> WindowInfo {
+        WindowInfo {
+            id: w.id.protocol_id(),
+            app_id: w.app_id.clone(),
+            title: w.title.clone(),
+            output: w.output.clone(),
+            tags: w.tags,
+            is_floating: w.is_floating,
+            is_fullscreen: w.is_fullscreen,
+            is_minimized: w.is_minimized,
+            is_sticky: w.is_sticky,
+            mark: w.mark.clone(),
+            focused: self.focused_window.as_ref() == Some(&w.id),
+        }
+    }
+
+    /// 导出所有显示器 → 标签 → 布局树，按名字与标签排序保证输出稳定
+    fn get_tree(&self) -> Vec<OutputTreeInfo> {
+        let hidden = self.hidden_tiled_windows();
+        let mut names: Vec<&String> = self.outputs.keys().collect();
+        names.sort();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let out_data = &self.outputs[name];
+                let on_output = |w: &&crate::wm::WindowData| w.output.as_ref() == Some(name);
+
+                // 有平铺树的标签 + 有悬浮窗口的标签 + 当前显示的标签
+                let mut tags: Vec<u32> = self
+                    .layout_roots
+                    .keys()
+                    .filter(|(out, _)| out == name)
+                    .map(|(_, tag)| *tag)
+                    .chain(
+                        self.windows
+                            .iter()
+                            .filter(on_output)
+                            .filter(|w| w.is_floating && !w.is_minimized)
+                            .map(|w| w.tags),
+                    )
+                    .chain(std::iter::once(out_data.tags))
+                    .collect();
+                tags.sort();
+                tags.dedup();
+
+                let tags = tags
+                    .into_iter()
+                    .map(|tag| {
+                        let tree_key = (name.clone(), tag);
+                        let layout = self.tag_layout(&tree_key);
+                        TagTreeInfo {
+                            tag,
+                            visible: tag == out_data.tags,
+                            layout,
+                            root: self.layout_roots.get(&tree_key).map(|root| {
+                                // 窗口的实际位置按布局模式计算，容器只反映树本身的切割
+                                let placed =
+                                    self.arrange_tag_windows(&tree_key, out_data.usable_area);
+                                let hints = self.tiled_size_hints(&tree_key);
+                                self.tree_node_info(
+                                    root,
+                                    out_data.usable_area,
+                                    &placed,
+                                    &hidden,
+                                    &hints,
+                                )
+                            }),
+                            floating: self
+                                .windows
+                                .iter()
+                                .filter(on_output)
+                                .filter(|w| w.is_floating && !w.is_minimized && w.tags == tag)
+                                .map(|w| self.window_info(w))
+                                .collect(),
+                        }
+                    })
+                    .collect();
+
+                OutputTreeInfo {
+                    name: name.clone(),
+                    focused: self.focused_output.as_ref() == Some(name),
+                    usable_area: out_data.usable_area,
+                    tags,
+                    minimized: self
+                        .windows
+                        .iter()
+                        .filter(on_output)
+                        .filter(|w| w.is_minimized)
+                        .map(|w| self.window_info(w))
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// 递归转换布局树，切割算法与 calculate_layout 共用 split_constrained；窗口位置取自 placed
+    fn tree_node_info(
+        &self,
+        node: &LayoutNode,
+        area: Geometry,
+        placed: &[(RiverWindowV1, Geometry)],
+        hidden: &[ObjectId],
+        hints: &HashMap<ObjectId, SizeHints>,
+    ) -> TreeNodeInfo {
+        match node {
+            LayoutNode::Window(w_data) => {
+                // 树里存的是插入时的拷贝，标题等信息以全局列表为准
+                let live = self
+                    .windows
+                    .iter()
+                    .find(|w| w.id == w_data.id)
+                    .unwrap_or(w_data);
+                TreeNodeInfo::Window {
+                    id: live.id.protocol_id(),
+                    app_id: live.app_id.clone(),
+                    title: live.title.clone(),
+                    geometry: placed
+                        .iter()
+                        .find(|(window, _)| window.id() == w_data.id)
+                        .map(|(_, geom)| *geom)
+                        .unwrap_or(area),
+                    hidden: hidden.contains(&w_data.id),
+                }
+            }
+            LayoutNode::Container {
+                split_type,
+                ratio,
+                left_child,
+                right_child,
+                mode,
+                ..
+            } => {
+                // tabbed/stacked 容器不切割，子节点都占满整个容器
+                let (left_area, right_area) = if *mode == ContainerMode::Split {
+                    split_constrained(area, *split_type, *ratio, left_child, right_child, hints)
+                } else {
+                    (area, area)
+                };
+                TreeNodeInfo::Container {
+                    split_type: *split_type,
+                    ratio: *ratio,
+                    mode: *mode,
+                    geometry: area,
+                    children: vec![
+                        self.tree_node_info(left_child, left_area, placed, hidden, hints),
+                        self.tree_node_info(right_child, right_area, placed, hidden, hints),
+                    ],
+                }
+            }
+        }
+    }
+
+    /// 导出所有显示器的模式列表、位置尺寸与标签状态
+    fn get_outputs(&self) -> Vec<OutputInfo> {
+        self.heads
+            .iter()
+            .map(|head| {
+                let modes: Vec<ModeDetail> = head
+                    .modes
+                    .iter()
+                    .map(|m| ModeDetail {
+                        width: m.width,
+                        height: m.height,
+                        refresh: m.refresh as f64 / 1000.0,
+                        current: head.current_mode.as_ref() == Some(&m.obj.id()),
+                    })
+                    .collect();
+                let out_data = self.outputs.get(&head.name);
+
+                OutputInfo {
+                    name: head.name.clone(),
+                    focused: self.focused_output.as_ref() == Some(&head.name),
+                    current_mode: modes.iter().find(|m| m.current).cloned(),
+                    modes,
+                    full_area: out_data.map(|o| o.full_area),
+                    usable_area: out_data.map(|o| o.usable_area),
+                    tags: out_data.map(|o| o.tags),
+                }
+            })
+            .collect()
+    }
+
+    /// 辅助：生成 AppID 报告字符串
+    fn get_app_ids_report(&self) -> String {
+        let mut report = String::from("ID\tAppID\t\tTitle\t\t\tTag\n");
+        report.push_str("--\t-----\t\t-----\t\t\t---\n");
+
+        for w in &self.windows {
+            let app_id = w.app_id.as_deref().unwrap_or("<Unknown>");
+            let title = w.title.as_deref().unwrap_or("<None>");
+            let id_raw = w.id.protocol_id();
+            let extra = if w.is_fullscreen {
+                "[Fullscreen]"
+            } else if w.is_floating {
+                "[Floating]"
+            } else {
+                ""
+            };
+
+            // 加入 title 打印
+            report.push_str(&format!(
+                "{}\t{}\t\t{}\t\tTag:{:b} {}\n",
+                id_raw, app_id, title, w.tags, extra
+            ));
+        }
+
+        if self.windows.is_empty() {
+            report.push_str("(No windows)\n");
+        }
+        report
+    }
+}
@@ -1,5 +1,7 @@
 use crate::protocol::river_wm::river_window_v1::RiverWindowV1;
 use crate::wm::WindowData;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use wayland_backend::client::ObjectId;
 
 // --- 调整轴向枚举 ---
@@ -9,12 +11,23 @@ pub enum ResizeAxis {
     Vertical,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SplitType {
     Horizontal,
     Vertical,
 }
 
+impl SplitType {
+    /// 左右并排 <-> 上下堆叠
+    pub fn flipped(self) -> Self {
+        match self {
+            SplitType::Horizontal => SplitType::Vertical,
+            SplitType::Vertical => SplitType::Horizontal,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Direction {
     Left,
@@ -23,7 +36,7 @@ pub enum Direction {
     Down,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Geometry {
     pub x: i32,
     pub y: i32,
@@ -31,6 +44,159 @@ pub struct Geometry {
     pub h: i32,
 }
 
+/// 每个 (显示器, 标签) 的布局模式：tree 为 BSP 树，master_stack 为 dwm/river-tile 风格的主从布局
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutMode {
+    Tree,
+    MasterStack,
+    Monocle,  // 整个标签一次只显示一个窗口，左右切换焦点时轮换
+    Scroller, // niri 风格：每个窗口一列，排成一条横向无限延伸的带子，视口跟随焦点滚动
+}
+
+impl LayoutMode {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "tree" | "bsp" => Ok(LayoutMode::Tree),
+            "master_stack" | "tile" => Ok(LayoutMode::MasterStack),
+            "monocle" => Ok(LayoutMode::Monocle),
+            "scroller" | "scrolling" => Ok(LayoutMode::Scroller),
+            _ => Err(format!(
+                "Unknown layout {} (expected tree, master_stack, monocle or scroller)",
+                name
+            )),
+        }
+    }
+}
+
+/// 主区所在的一侧
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MasterLocation {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl MasterLocation {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "left" => Ok(MasterLocation::Left),
+            "right" => Ok(MasterLocation::Right),
+            "top" => Ok(MasterLocation::Top),
+            "bottom" => Ok(MasterLocation::Bottom),
+            _ => Err(format!(
+                "Unknown master location {} (expected left, right, top or bottom)",
+                name
+            )),
+        }
+    }
+}
+
+/// 某个标签当前的布局参数，master_* 只在 master_stack 模式下生效
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TagLayout {
+    pub mode: LayoutMode,
+    pub master_count: u32,
+    pub master_ratio: f32,
+    pub master_location: MasterLocation,
+}
+
+impl Default for TagLayout {
+    fn default() -> Self {
+        TagLayout {
+            mode: LayoutMode::Tree,
+            master_count: 1,
+            master_ratio: 0.55,
+            master_location: MasterLocation::Left,
+        }
+    }
+}
+
+/// 容器模式：split 为左右/上下并排，tabbed/stacked 时整个子树一次只显示一个窗口 (i3 风格)
+/// tabbed 用左右切换焦点轮换，stacked 用上下
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerMode {
+    Split,
+    Tabbed,
+    Stacked,
+}
+
+impl ContainerMode {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "split" => Ok(ContainerMode::Split),
+            "tabbed" => Ok(ContainerMode::Tabbed),
+            "stacked" => Ok(ContainerMode::Stacked),
+            _ => Err(format!(
+                "Unknown container mode {} (expected split, tabbed or stacked)",
+                name
+            )),
+        }
+    }
+}
+
+/// tree 布局插入新窗口时的自动切分策略，[window] split_policy 全局或按标签配置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitPolicy {
+    LongestSide, // 沿目标窗口较长的一边切分 (默认)
+    Dwindle,     // 与父容器的方向交替，新窗口总在右/下
+    Spiral,      // 与 dwindle 一样交替，但新窗口的位置按 右、下、左、上 旋转
+    Grid,        // 窗口数量变化时把整棵树重建成均匀的网格
+}
+
+impl SplitPolicy {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().replace('-', "_").as_str() {
+            "longest_side" | "auto" => Ok(SplitPolicy::LongestSide),
+            "dwindle" | "alternate" => Ok(SplitPolicy::Dwindle),
+            "spiral" => Ok(SplitPolicy::Spiral),
+            "grid" => Ok(SplitPolicy::Grid),
+            _ => Err(format!(
+                "Unknown split policy {} (expected longest_side, dwindle, spiral or grid)",
+                name
+            )),
+        }
+    }
+}
+
+/// 手动预选 (bspwm 风格)：下一个平铺到该窗口旁边的新窗口放在 dir 一侧，
+/// ratio 为新窗口占的比例，不填时对半分
+#[derive(Debug, Clone)]
+pub struct Preselection {
+    pub window: ObjectId,
+    pub dir: Direction,
+    pub ratio: Option<f32>,
+}
+
+/// 树结构调整，作用于焦点窗口的父容器或整个标签
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeOp {
+    Rotate(bool),     // 旋转 90°，true 为顺时针
+    Flip(ResizeAxis), // Horizontal 为左右镜像，Vertical 为上下镜像
+    Balance,          // 所有比例按窗口数均分
+    ToggleSplit,      // 只切换这一层容器的方向
+}
+
+/// 树结构调整的作用范围
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeScope {
+    Parent,
+    Tag,
+}
+
+impl TreeScope {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "parent" => Ok(TreeScope::Parent),
+            "tag" | "root" | "all" => Ok(TreeScope::Tag),
+            _ => Err(format!("Unknown scope {} (expected parent or tag)", name)),
+        }
+    }
+}
+
 // --- 递归查找的反馈状态 ---
 #[derive(Debug, PartialEq)]
 pub enum ResizeResult {
@@ -46,26 +212,53 @@ pub enum LayoutNode {
         ratio: f32,
         left_child: Box<LayoutNode>,
         right_child: Box<LayoutNode>,
+        mode: ContainerMode,
+        active: Option<ObjectId>, // tabbed/stacked 最后显示的窗口，焦点离开容器后仍显示它
     },
 }
 
 impl LayoutNode {
+    /// 新建一个普通的并排容器
+    pub fn split(split_type: SplitType, ratio: f32, left: LayoutNode, right: LayoutNode) -> Self {
+        LayoutNode::Container {
+            split_type,
+            ratio,
+            left_child: Box::new(left),
+            right_child: Box::new(right),
+            mode: ContainerMode::Split,
+            active: None,
+        }
+    }
+
     pub fn insert_at(
         &mut self,
         target_id: &ObjectId,
         new_win: WindowData,
         split: SplitType,
         custom_ratio: Option<f32>,
+    ) -> bool {
+        self.insert_beside(target_id, new_win, split, custom_ratio, false)
+    }
+
+    /// 切分目标窗口，new_first 为 true 时新窗口放在左/上 (spiral)；
+    /// custom_ratio 始终是目标窗口保留的比例
+    pub fn insert_beside(
+        &mut self,
+        target_id: &ObjectId,
+        new_win: WindowData,
+        split: SplitType,
+        custom_ratio: Option<f32>,
+        new_first: bool,
     ) -> bool {
         match self {
             LayoutNode::Window(w_data) => {
                 if &w_data.id == target_id {
-                    let old_win = w_data.clone();
-                    *self = LayoutNode::Container {
-                        split_type: split,
-                        ratio: custom_ratio.unwrap_or(0.5),
-                        left_child: Box::new(LayoutNode::Window(old_win)),
-                        right_child: Box::new(LayoutNode::Window(new_win)),
+                    let old_win = LayoutNode::Window(w_data.clone());
+                    let ratio = custom_ratio.unwrap_or(0.5);
+                    *self = if new_first {
+                        LayoutNode::split(split, 1.0 - ratio, LayoutNode::Window(new_win), old_win)
+                    } else {
+                        LayoutNode::split(split, ratio, old_win, LayoutNode::Window(new_win))
                     };
                     return true;
                 }
@@ -76,12 +269,57 @@ impl LayoutNode {
                 right_child,
                 ..
             } => {
-                left_child.insert_at(target_id, new_win.clone(), split, custom_ratio)
-                    || right_child.insert_at(target_id, new_win, split, custom_ratio)
+                left_child.insert_beside(target_id, new_win.clone(), split, custom_ratio, new_first)
+                    || right_child.insert_beside(target_id, new_win, split, custom_ratio, new_first)
+            }
+        }
+    }
+
+    /// 从根到目标窗口途经的每个容器的切割方向，找不到目标时返回 None
+    pub fn split_path(&self, target_id: &ObjectId) -> Option<Vec<SplitType>> {
+        match self {
+            LayoutNode::Window(w_data) => (&w_data.id == target_id).then(Vec::new),
+            LayoutNode::Container {
+                split_type,
+                left_child,
+                right_child,
+                ..
+            } => {
+                let mut path = left_child
+                    .split_path(target_id)
+                    .or_else(|| right_child.split_path(target_id))?;
+                path.insert(0, *split_type);
+                Some(path)
             }
         }
     }
 
+    /// 把窗口按顺序排成尽量方正的网格：先按行上下切分，每行再左右均分
+    pub fn grid(windows: Vec<WindowData>) -> Option<LayoutNode> {
+        let cols = (windows.len() as f32).sqrt().ceil().max(1.0) as usize;
+        let rows = windows
+            .chunks(cols)
+            .filter_map(|row| {
+                let cells = row.iter().cloned().map(LayoutNode::Window).collect();
+                Self::chain_even(cells, SplitType::Vertical)
+            })
+            .collect();
+        Self::chain_even(rows, SplitType::Horizontal)
+    }
+
+    /// 把若干节点串成一条链，每个节点分到相同的宽度 (或高度)
+    fn chain_even(mut nodes: Vec<LayoutNode>, split_type: SplitType) -> Option<LayoutNode> {
+        if nodes.is_empty() {
+            return None;
+        }
+        let count = nodes.len();
+        let first = nodes.remove(0);
+        Some(match Self::chain_even(nodes, split_type) {
+            Some(rest) => LayoutNode::split(split_type, 1.0 / count as f32, first, rest),
+            None => first,
+        })
+    }
+
     pub fn remove_at(node: LayoutNode, target_id: &ObjectId) -> Option<LayoutNode> {
         match node {
             LayoutNode::Window(w_data) => {
@@ -96,6 +334,8 @@ impl LayoutNode {
                 ratio,
                 left_child,
                 right_child,
+                mode,
+                active,
             } => {
                 let new_left = Self::remove_at(*left_child, target_id);
                 let new_right = Self::remove_at(*right_child, target_id);
@@ -105,6 +345,8 @@ impl LayoutNode {
                         ratio,
                         left_child: Box::new(l),
                         right_child: Box::new(r),
+                        mode,
+                        active,
                     }),
                     (None, Some(r)) => Some(r),
                     (Some(l), None) => Some(l),
@@ -162,6 +404,33 @@ impl LayoutNode {
         }
     }
 
+    /// 用另一个窗口顶替目标叶子，树的形状和比例保持不变
+    pub fn replace_window(&mut self, target_id: &ObjectId, new_win: WindowData) -> bool {
+        match self {
+            LayoutNode::Window(w_data) => {
+                if &w_data.id == target_id {
+                    *w_data = new_win;
+                    return true;
+                }
+                false
+            }
+            LayoutNode::Container {
+                left_child,
+                right_child,
+                active,
+                ..
+            } => {
+                let new_id = new_win.id.clone();
+                let found = left_child.replace_window(target_id, new_win.clone())
+                    || right_child.replace_window(target_id, new_win);
+                if found && active.as_ref() == Some(target_id) {
+                    *active = Some(new_id);
+                }
+                found
+            }
+        }
+    }
+
     // --- 核心 BSP 树调整算法 ---
     pub fn apply_resize(
         &mut self,
@@ -184,29 +453,10 @@ impl LayoutNode {
                 ratio,
                 left_child,
                 right_child,
+                ..
             } => {
                 // 1. 预先计算子区域尺寸，以便后面能算出准确的像素比例
-                let (left_area, right_area) = if *split_type == SplitType::Vertical {
-                    let left_w = (area.w as f32 * *ratio) as i32;
-                    (
-                        Geometry { w: left_w, ..area },
-                        Geometry {
-                            x: area.x + left_w,
-                            w: area.w - left_w,
-                            ..area
-                        },
-                    )
-                } else {
-                    let top_h = (area.h as f32 * *ratio) as i32;
-                    (
-                        Geometry { h: top_h, ..area },
-                        Geometry {
-                            y: area.y + top_h,
-                            h: area.h - top_h,
-                            ..area
-                        },
-                    )
-                };
+                let (left_area, right_area) = split_area(area, *split_type, *ratio);
 
                 // 2. 递归寻找目标，先找左边，再找右边
                 let mut res = left_child.apply_resize(target_id, left_area, axis, delta);
@@ -264,6 +514,219 @@ impl LayoutNode {
             }
         }
     }
+    /// 按从左到右 (从上到下) 的顺序列出树里的窗口，master_stack 用它作为窗口顺序
+    pub fn leaves(&self) -> Vec<&WindowData> {
+        match self {
+            LayoutNode::Window(w_data) => vec![w_data],
+            LayoutNode::Container {
+                left_child,
+                right_child,
+                ..
+            } => {
+                let mut list = left_child.leaves();
+                list.extend(right_child.leaves());
+                list
+            }
+        }
+    }
+
+    /// 修改包含目标窗口的最内层容器的模式，目标独占整棵树时没有容器可改
+    pub fn set_parent_mode(&mut self, target_id: &ObjectId, new_mode: ContainerMode) -> bool {
+        match self {
+            LayoutNode::Window(_) => false,
+            LayoutNode::Container {
+                left_child,
+                right_child,
+                mode,
+                ..
+            } => {
+                if left_child.set_parent_mode(target_id, new_mode)
+                    || right_child.set_parent_mode(target_id, new_mode)
+                {
+                    return true;
+                }
+                let is_parent = [&**left_child, &**right_child]
+                    .iter()
+                    .any(|c| matches!(c, LayoutNode::Window(w) if &w.id == target_id));
+                if is_parent {
+                    *mode = new_mode;
+                }
+                is_parent
+            }
+        }
+    }
+
+    /// 对这个节点做结构调整：toggle_split 只改这一层，其余递归作用于整棵子树
+    pub fn apply_tree_op(&mut self, op: TreeOp) {
+        let (left_count, right_count) = match self {
+            LayoutNode::Window(_) => return,
+            LayoutNode::Container {
+                left_child,
+                right_child,
+                ..
+            } => (left_child.leaves().len(), right_child.leaves().len()),
+        };
+        if let LayoutNode::Container {
+            split_type,
+            ratio,
+            left_child,
+            right_child,
+            ..
+        } = self
+        {
+            let swap = match op {
+                // 顺时针时上下堆叠的容器变成左右并排要交换两侧 (上 -> 右)，逆时针则相反
+                TreeOp::Rotate(clockwise) => {
+                    let swap = (*split_type == SplitType::Horizontal) == clockwise;
+                    *split_type = split_type.flipped();
+                    swap
+                }
+                TreeOp::Flip(axis) => matches!(
+                    (axis, *split_type),
+                    (ResizeAxis::Horizontal, SplitType::Vertical)
+                        | (ResizeAxis::Vertical, SplitType::Horizontal)
+                ),
+                TreeOp::Balance => {
+                    *ratio = left_count as f32 / (left_count + right_count) as f32;
+                    false
+                }
+                TreeOp::ToggleSplit => {
+                    *split_type = split_type.flipped();
+                    return;
+                }
+            };
+            if swap {
+                std::mem::swap(left_child, right_child);
+                *ratio = 1.0 - *ratio;
+            }
+            left_child.apply_tree_op(op);
+            right_child.apply_tree_op(op);
+        }
+    }
+
+    /// 对直接包含目标窗口的容器做结构调整，目标独占整棵树时返回 false
+    pub fn apply_to_parent(&mut self, target_id: &ObjectId, op: TreeOp) -> bool {
+        let is_parent = match self {
+            LayoutNode::Window(_) => return false,
+            LayoutNode::Container {
+                left_child,
+                right_child,
+                ..
+            } => [&**left_child, &**right_child]
+                .iter()
+                .any(|c| matches!(c, LayoutNode::Window(w) if &w.id == target_id)),
+        };
+        if is_parent {
+            self.apply_tree_op(op);
+            return true;
+        }
+        match self {
+            LayoutNode::Window(_) => false,
+            LayoutNode::Container {
+                left_child,
+                right_child,
+                ..
+            } => {
+                left_child.apply_to_parent(target_id, op)
+                    || right_child.apply_to_parent(target_id, op)
+            }
+        }
+    }
+
+    /// 焦点落在 tabbed/stacked 容器里时记下它，返回该子树是否包含这个窗口
+    pub fn remember_active(&mut self, focused_id: &ObjectId) -> bool {
+        match self {
+            LayoutNode::Window(w_data) => &w_data.id == focused_id,
+            LayoutNode::Container {
+                left_child,
+                right_child,
+                mode,
+                active,
+                ..
+            } => {
+                let found = left_child.remember_active(focused_id)
+                    || right_child.remember_active(focused_id);
+                if found && *mode != ContainerMode::Split {
+                    *active = Some(focused_id.clone());
+                }
+                found
+            }
+        }
+    }
+
+    /// 找到包含目标窗口的最外层 tabbed/stacked 容器，返回它的模式与全部窗口 (即它的标签页)
+    pub fn tab_group(&self, target_id: &ObjectId) -> Option<(ContainerMode, Vec<ObjectId>)> {
+        match self {
+            LayoutNode::Window(_) => None,
+            LayoutNode::Container {
+                left_child,
+                right_child,
+                mode,
+                ..
+            } => {
+                let ids: Vec<ObjectId> = self.leaves().iter().map(|w| w.id.clone()).collect();
+                if !ids.contains(target_id) {
+                    return None;
+                }
+                if *mode != ContainerMode::Split {
+                    return Some((*mode, ids));
+                }
+                left_child
+                    .tab_group(target_id)
+                    .or_else(|| right_child.tab_group(target_id))
+            }
+        }
+    }
+
+    /// 当前被藏起来的窗口：monocle 下除了 shown 以外的全部窗口，
+    /// 其余模式下 tabbed/stacked 容器里没在显示的窗口 (master_stack 不区分容器)
+    pub fn hidden_windows(
+        &self,
+        layout_mode: LayoutMode,
+        shown: Option<&ObjectId>,
+    ) -> Vec<ObjectId> {
+        let ids: Vec<ObjectId> = self.leaves().iter().map(|w| w.id.clone()).collect();
+        match layout_mode {
+            LayoutMode::Monocle => {
+                let visible = shown.filter(|id| ids.contains(id)).or(ids.first()).cloned();
+                ids.into_iter()
+                    .filter(|id| Some(id) != visible.as_ref())
+                    .collect()
+            }
+            // 滚动布局藏起来的是视口外的列，需要知道列宽与偏移，由调用方计算
+            LayoutMode::MasterStack | LayoutMode::Scroller => Vec::new(),
+            LayoutMode::Tree => {
+                let mut hidden = Vec::new();
+                self.collect_hidden_tabs(&mut hidden);
+                hidden
+            }
+        }
+    }
+
+    fn collect_hidden_tabs(&self, hidden: &mut Vec<ObjectId>) {
+        if let LayoutNode::Container {
+            left_child,
+            right_child,
+            mode,
+            active,
+            ..
+        } = self
+        {
+            if *mode == ContainerMode::Split {
+                left_child.collect_hidden_tabs(hidden);
+                right_child.collect_hidden_tabs(hidden);
+                return;
+            }
+            let ids: Vec<ObjectId> = self.leaves().iter().map(|w| w.id.clone()).collect();
+            let visible = active
+                .as_ref()
+                .filter(|id| ids.contains(id))
+                .or(ids.first())
+                .cloned();
+            hidden.extend(ids.into_iter().filter(|id| Some(id) != visible.as_ref()));
+        }
+    }
+
     // --- 允许更新已入树窗口的分割比例 ---
     pub fn update_ratio_for_new_window(&mut self, target_id: &ObjectId, new_ratio: f32) -> bool {
         match self {
@@ -294,44 +757,441 @@ impl LayoutNode {
     }
 }
 
+/// 按容器的切割方向与比例，把区域一分为二 (左/上, 右/下)
+pub fn split_area(area: Geometry, split_type: SplitType, ratio: f32) -> (Geometry, Geometry) {
+    let total = if split_type == SplitType::Vertical {
+        area.w
+    } else {
+        area.h
+    };
+    split_area_at(area, split_type, (total as f32 * ratio) as i32)
+}
+
+/// 按像素切割：左/上半部分占 first 像素，其余给右/下
+fn split_area_at(area: Geometry, split_type: SplitType, first: i32) -> (Geometry, Geometry) {
+    if split_type == SplitType::Vertical {
+        (
+            Geometry { w: first, ..area },
+            Geometry {
+                x: area.x + first,
+                w: area.w - first,
+                ..area
+            },
+        )
+    } else {
+        (
+            Geometry { h: first, ..area },
+            Geometry {
+                y: area.y + first,
+                h: area.h - first,
+                ..area
+            },
+        )
+    }
+}
+
+/// 平铺窗口的间隙：inner 为相邻窗口之间，top/right/bottom/left 为到可用区域边缘
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Gaps {
+    pub inner: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+impl Gaps {
+    /// 外间隙按 CSS 的写法：1 个值四边相同，2 个值为 上下/左右，4 个值为 上 右 下 左
+    pub fn parse_outer(value: &str) -> Result<[i32; 4], String> {
+        let nums = value
+            .split_whitespace()
+            .map(|s| s.parse::<i32>().map(|n| n.max(0)))
+            .collect::<Result<Vec<i32>, _>>()
+            .map_err(|_| format!("Invalid outer gaps {:?}", value))?;
+        match nums[..] {
+            [all] => Ok([all; 4]),
+            [vertical, horizontal] => Ok([vertical, horizontal, vertical, horizontal]),
+            [top, right, bottom, left] => Ok([top, right, bottom, left]),
+            _ => Err(format!(
+                "Outer gaps {:?} need 1, 2 or 4 values (top right bottom left)",
+                value
+            )),
+        }
+    }
+
+    /// 窗口四周的缩进 (左, 右, 上, 下)：贴着可用区域边缘的一侧用外间隙，其余用内间隙的一半
+    pub fn offsets(&self, geom: Geometry, screen: Geometry) -> (i32, i32, i32, i32) {
+        let half = (self.inner as f32 / 2.0).ceil() as i32;
+        let pick = |at_edge: bool, outer: i32| if at_edge { outer } else { half };
+        (
+            pick(geom.x == screen.x, self.left),
+            pick(geom.x + geom.w == screen.x + screen.w, self.right),
+            pick(geom.y == screen.y, self.top),
+            pick(geom.y + geom.h == screen.y + screen.h, self.bottom),
+        )
+    }
+}
+
+/// 客户端通过 DimensionsHint 声明的尺寸限制，0 表示不限制
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SizeHints {
+    pub min_w: i32,
+    pub min_h: i32,
+    pub max_w: i32,
+    pub max_h: i32,
+}
+
+impl SizeHints {
+    /// 格子比窗口的最大尺寸还大时 (例如它是唯一的窗口)，按最大尺寸在格子里居中
+    pub fn fit(&self, geo: Geometry) -> Geometry {
+        let w = geo.w.min(self.along(SplitType::Vertical).1);
+        let h = geo.h.min(self.along(SplitType::Horizontal).1);
+        Geometry {
+            x: geo.x + (geo.w - w) / 2,
+            y: geo.y + (geo.h - h) / 2,
+            w,
+            h,
+        }
+    }
+
+    /// 沿切割方向的 (最小, 最大) 尺寸，没有上限时为 i32::MAX
+    fn along(&self, split_type: SplitType) -> (i32, i32) {
+        let (min, max) = if split_type == SplitType::Vertical {
+            (self.min_w, self.max_w)
+        } else {
+            (self.min_h, self.max_h)
+        };
+        (min.max(0), if max > 0 { max.max(min) } else { i32::MAX })
+    }
+}
+
+/// 子树沿切割方向能接受的 (最小, 最大) 尺寸：
+/// 同方向切开的两半相加，垂直方向切开或标签页叠放的取最严格的那个
+fn subtree_limits(
+    node: &LayoutNode,
+    split_type: SplitType,
+    hints: &HashMap<ObjectId, SizeHints>,
+) -> (i32, i32) {
+    match node {
+        LayoutNode::Window(w_data) => hints
+            .get(&w_data.id)
+            .map(|h| h.along(split_type))
+            .unwrap_or((0, i32::MAX)),
+        LayoutNode::Container {
+            split_type: own,
+            mode,
+            left_child,
+            right_child,
+            ..
+        } => combine_limits(
+            subtree_limits(left_child, split_type, hints),
+            subtree_limits(right_child, split_type, hints),
+            *mode == ContainerMode::Split && *own == split_type,
+        ),
+    }
+}
+
+/// 合并两个子树的限制：stacked 为 true 时两半沿切割方向首尾相接，否则叠在同一块区域上
+fn combine_limits(left: (i32, i32), right: (i32, i32), stacked: bool) -> (i32, i32) {
+    let ((lmin, lmax), (rmin, rmax)) = (left, right);
+    if stacked {
+        (lmin.saturating_add(rmin), lmax.saturating_add(rmax))
+    } else {
+        let min = lmin.max(rmin);
+        (min, lmax.min(rmax).max(min))
+    }
+}
+
+/// 按比例切割，但让两半都满足子树的尺寸限制：比例只是期望值，相邻的一侧吸收差额。
+/// 限制互相冲突时优先保证最小尺寸，连最小尺寸都放不下时按最小尺寸的比例分配
+pub fn split_constrained(
+    area: Geometry,
+    split_type: SplitType,
+    ratio: f32,
+    left: &LayoutNode,
+    right: &LayoutNode,
+    hints: &HashMap<ObjectId, SizeHints>,
+) -> (Geometry, Geometry) {
+    if hints.is_empty() {
+        return split_area(area, split_type, ratio);
+    }
+    let total = if split_type == SplitType::Vertical {
+        area.w
+    } else {
+        area.h
+    }
+    .max(0);
+    let first = constrained_first(
+        total,
+        (total as f32 * ratio) as i32,
+        subtree_limits(left, split_type, hints),
+        subtree_limits(right, split_type, hints),
+    );
+    split_area_at(area, split_type, first)
+}
+
+/// 左/上半部分的像素数：desired 为按比例得到的期望值，left/right 为两侧的 (最小, 最大) 尺寸
+fn constrained_first(total: i32, desired: i32, left: (i32, i32), right: (i32, i32)) -> i32 {
+    let ((lmin, lmax), (rmin, rmax)) = (left, right);
+    let lo = lmin.max(total.saturating_sub(rmax));
+    let hi = lmax.min(total - rmin);
+    if lo <= hi {
+        desired.clamp(lo, hi)
+    } else if lmin.saturating_add(rmin) > total {
+        (total as i64 * lmin as i64 / (lmin as i64 + rmin as i64)) as i32
+    } else {
+        desired.clamp(lmin, total - rmin)
+    }
+}
+
 pub fn calculate_layout(
     node: &LayoutNode,
     area: Geometry,
+    hints: &HashMap<ObjectId, SizeHints>,
     results: &mut Vec<(RiverWindowV1, Geometry)>,
 ) {
     match node {
         LayoutNode::Window(w_data) => results.push((w_data.window.clone(), area)),
+        // tabbed/stacked：每个标签页都铺满整个容器，切换时不需要重新调整尺寸
+        LayoutNode::Container { mode, .. } if *mode != ContainerMode::Split => {
+            for w_data in node.leaves() {
+                results.push((w_data.window.clone(), area));
+            }
+        }
         LayoutNode::Container {
             split_type,
             ratio,
             left_child,
             right_child,
+            ..
         } => {
-            if *split_type == SplitType::Vertical {
-                let left_w = (area.w as f32 * ratio) as i32;
-                calculate_layout(left_child, Geometry { w: left_w, ..area }, results);
-                calculate_layout(
-                    right_child,
-                    Geometry {
-                        x: area.x + left_w,
-                        w: area.w - left_w,
-                        ..area
-                    },
-                    results,
-                );
-            } else {
-                let top_h = (area.h as f32 * ratio) as i32;
-                calculate_layout(left_child, Geometry { h: top_h, ..area }, results);
-                calculate_layout(
-                    right_child,
-                    Geometry {
-                        y: area.y + top_h,
-                        h: area.h - top_h,
-                        ..area
-                    },
-                    results,
-                );
+            let (left_area, right_area) =
+                split_constrained(area, *split_type, *ratio, left_child, right_child, hints);
+            calculate_layout(left_child, left_area, hints, results);
+            calculate_layout(right_child, right_area, hints, results);
+        }
+    }
+}
+
+/// 滚动布局的视口：每列的像素宽度 (与 leaves 顺序一致) 与视口相对带子起点的偏移
+#[derive(Debug, Clone, Default)]
+pub struct ScrollView {
+    pub column_widths: Vec<i32>,
+    pub offset: i32,
+}
+
+/// 按标签的布局模式计算平铺窗口的位置，scroll 只在 scroller 模式下使用，hints 只在 tree 模式下使用
+pub fn arrange_tag(
+    node: &LayoutNode,
+    layout: &TagLayout,
+    area: Geometry,
+    scroll: &ScrollView,
+    hints: &HashMap<ObjectId, SizeHints>,
+    results: &mut Vec<(RiverWindowV1, Geometry)>,
+) {
+    match layout.mode {
+        LayoutMode::Tree => calculate_layout(node, area, hints, results),
+        LayoutMode::MasterStack => calculate_master_stack(node, layout, area, results),
+        LayoutMode::Monocle => {
+            // 所有窗口都铺满，只有一个可见 (见 hidden_windows)
+            for w_data in node.leaves() {
+                results.push((w_data.window.clone(), area));
+            }
+        }
+        LayoutMode::Scroller => calculate_scroller(node, scroll, area, results),
+    }
+}
+
+/// 滚动布局：列从左到右依次排开，超出显示器的部分由 clip box 裁掉
+fn calculate_scroller(
+    node: &LayoutNode,
+    scroll: &ScrollView,
+    area: Geometry,
+    results: &mut Vec<(RiverWindowV1, Geometry)>,
+) {
+    let mut x = area.x - scroll.offset;
+    for (i, w_data) in node.leaves().into_iter().enumerate() {
+        let w = scroll.column_widths.get(i).copied().unwrap_or(area.w / 2);
+        results.push((w_data.window.clone(), Geometry { x, w, ..area }));
+        x += w;
+    }
+}
+
+/// 让第 index 列完整出现在视口里，只滚动必要的距离 (列比视口还宽时对齐左边)
+pub fn scroll_to_column(scroll: &ScrollView, index: usize, view_w: i32) -> i32 {
+    let col_x: i32 = scroll.column_widths.iter().take(index).sum();
+    let col_w = scroll.column_widths.get(index).copied().unwrap_or(0);
+    let total: i32 = scroll.column_widths.iter().sum();
+
+    let mut offset = scroll.offset;
+    if col_x + col_w > offset + view_w {
+        offset = col_x + col_w - view_w;
+    }
+    if col_x < offset {
+        offset = col_x;
+    }
+    // 带子总长不足一屏时不留空白
+    offset.clamp(0, (total - view_w).max(0))
+}
+
+/// 主从布局：前 master_count 个窗口平分主区，其余窗口平分栈区
+fn calculate_master_stack(
+    node: &LayoutNode,
+    layout: &TagLayout,
+    area: Geometry,
+    results: &mut Vec<(RiverWindowV1, Geometry)>,
+) {
+    let windows = node.leaves();
+    let masters = (layout.master_count as usize).min(windows.len());
+    let stacks = windows.len() - masters;
+
+    // 主区与栈区沿 master_location 的方向切开，区内窗口沿另一条轴排列
+    let (split_type, inner_split) = match layout.master_location {
+        MasterLocation::Left | MasterLocation::Right => {
+            (SplitType::Vertical, SplitType::Horizontal)
+        }
+        MasterLocation::Top | MasterLocation::Bottom => {
+            (SplitType::Horizontal, SplitType::Vertical)
+        }
+    };
+    let (master_area, stack_area) = if masters == 0 || stacks == 0 {
+        (area, area)
+    } else {
+        match layout.master_location {
+            MasterLocation::Left | MasterLocation::Top => {
+                split_area(area, split_type, layout.master_ratio)
+            }
+            MasterLocation::Right | MasterLocation::Bottom => {
+                let (stack, master) = split_area(area, split_type, 1.0 - layout.master_ratio);
+                (master, stack)
             }
         }
+    };
+
+    let cells = split_even(master_area, inner_split, masters)
+        .into_iter()
+        .chain(split_even(stack_area, inner_split, stacks));
+    for (w_data, geom) in windows.into_iter().zip(cells) {
+        results.push((w_data.window.clone(), geom));
+    }
+}
+
+/// 把区域沿指定方向平分成 count 份，余下的像素分给后面的窗口
+fn split_even(area: Geometry, split_type: SplitType, count: usize) -> Vec<Geometry> {
+    let count = count as i32;
+    (0..count)
+        .map(|i| {
+            if split_type == SplitType::Vertical {
+                let x0 = area.w * i / count;
+                let x1 = area.w * (i + 1) / count;
+                Geometry {
+                    x: area.x + x0,
+                    w: x1 - x0,
+                    ..area
+                }
+            } else {
+                let y0 = area.h * i / count;
+                let y1 = area.h * (i + 1) / count;
+                Geometry {
+                    y: area.y + y0,
+                    h: y1 - y0,
+                    ..area
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONE: (i32, i32) = (0, i32::MAX);
+
+    #[test]
+    fn constrained_first_follows_ratio_within_limits() {
+        assert_eq!(constrained_first(1000, 500, NONE, NONE), 500);
+        assert_eq!(constrained_first(1000, 500, (600, i32::MAX), NONE), 600);
+        assert_eq!(constrained_first(1000, 500, NONE, (0, 300)), 700);
+    }
+
+    #[test]
+    fn constrained_first_splits_by_min_when_mins_overflow() {
+        // lmin + rmin > total：按最小尺寸的比例分配
+        assert_eq!(
+            constrained_first(1000, 500, (700, i32::MAX), (600, i32::MAX)),
+            538
+        );
+        assert_eq!(constrained_first(0, 0, (100, 200), (100, 200)), 0);
+    }
+
+    #[test]
+    fn constrained_first_drops_max_when_limits_conflict() {
+        // lo > hi 但最小尺寸放得下：放弃最大尺寸，只保证最小尺寸
+        assert_eq!(constrained_first(1000, 500, (0, 100), (0, 200)), 500);
+        assert_eq!(constrained_first(1000, 900, (0, 100), (300, 400)), 700);
+    }
+
+    #[test]
+    fn combine_limits_adds_along_split_and_intersects_across() {
+        assert_eq!(
+            combine_limits((100, 200), (50, i32::MAX), true),
+            (150, i32::MAX)
+        );
+        assert_eq!(
+            combine_limits((100, i32::MAX), (50, 400), false),
+            (100, 400)
+        );
+        // 最大尺寸比另一侧的最小尺寸还小时，以最小尺寸为准
+        assert_eq!(combine_limits((100, 200), (300, 400), false), (300, 300));
+    }
+
+    #[test]
+    fn size_hints_along_treats_zero_as_unbounded() {
+        let hints = SizeHints {
+            min_w: 50,
+            min_h: 500,
+            max_w: 0,
+            max_h: 300,
+        };
+        assert_eq!(hints.along(SplitType::Vertical), (50, i32::MAX));
+        assert_eq!(hints.along(SplitType::Horizontal), (500, 500));
+    }
+
+    #[test]
+    fn parse_outer_shorthand() {
+        assert_eq!(Gaps::parse_outer("10"), Ok([10; 4]));
+        assert_eq!(Gaps::parse_outer("5 10"), Ok([5, 10, 5, 10]));
+        assert_eq!(Gaps::parse_outer(" 1 2  3 4 "), Ok([1, 2, 3, 4]));
+        assert_eq!(Gaps::parse_outer("-3"), Ok([0; 4]));
+    }
+
+    #[test]
+    fn parse_outer_rejects_bad_values() {
+        assert!(Gaps::parse_outer("").is_err());
+        assert!(Gaps::parse_outer("1 2 3").is_err());
+        assert!(Gaps::parse_outer("1 2 3 4 5").is_err());
+        assert!(Gaps::parse_outer("10px").is_err());
+    }
+
+    #[test]
+    fn offsets_use_outer_gaps_only_at_edges() {
+        let gaps = Gaps {
+            inner: 9,
+            top: 1,
+            right: 2,
+            bottom: 3,
+            left: 4,
+        };
+        let screen = Geometry {
+            x: 0,
+            y: 0,
+            w: 1000,
+            h: 800,
+        };
+        let (left, right) = split_area(screen, SplitType::Vertical, 0.5);
+        assert_eq!(gaps.offsets(left, screen), (4, 5, 1, 3));
+        assert_eq!(gaps.offsets(right, screen), (5, 2, 1, 3));
+        assert_eq!(gaps.offsets(screen, screen), (4, 2, 1, 3));
     }
 }
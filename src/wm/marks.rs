@@ -0,0 +1,168 @@
+use crate::wm::actions::Action;
+use crate::wm::AppState;
+use tracing::{info, warn};
+use wayland_backend::client::ObjectId;
+
+impl AppState {
+    fn marked_window(&self, name: &str) -> Option<ObjectId> {
+        self.windows
+            .iter()
+            .find(|w| w.mark.as_deref() == Some(name))
+            .map(|w| w.id.clone())
+    }
+
+    /// 给窗口打上标记；同名标记只能属于一个窗口，原来的主人会失去它
+    pub fn set_mark(&mut self, win_id: &ObjectId, name: &str) {
+        for w in self.windows.iter_mut() {
+            if &w.id == win_id {
+                w.mark = Some(name.to_string());
+            } else if w.mark.as_deref() == Some(name) {
+                w.mark = None;
+            }
+        }
+        info!("-> [Mark] {:?} marked as {}", win_id, name);
+    }
+
+    pub fn unmark(&mut self, win_id: &ObjectId) {
+        if let Some(w) = self.windows.iter_mut().find(|w| &w.id == win_id) {
+            if let Some(name) = w.mark.take() {
+                info!("-> [Mark] Removed mark {} from {:?}", name, win_id);
+            }
+        }
+    }
+
+    /// 跳到带标记的窗口
+    pub fn focus_mark(&mut self, name: &str) {
+        match self.marked_window(name) {
+            Some(id) => self.focus_window(&id),
+            None => warn!("-> [Mark] No window marked as {}", name),
+        }
+    }
+
+    /// 聚焦任意窗口：需要时先切到它所在的显示器和标签
+    pub fn focus_window(&mut self, win_id: &ObjectId) {
+        let (out_name, win_tags, is_floating, float_geo) =
+            match self.windows.iter().find(|w| &w.id == win_id) {
+                Some(w) if w.is_minimized => {
+                    warn!("-> [Focus] {:?} is hidden, restore it first", win_id);
+                    return;
+                }
+                Some(w) => match &w.output {
+                    Some(out) => (out.clone(), w.tags, w.is_floating, w.float_geo),
+                    None => return,
+                },
+                None => return,
+            };
+        let out_tags = match self.outputs.get(&out_name) {
+            Some(o) => o.tags,
+            None => return,
+        };
+
+        if self.focused_output.as_ref() != Some(&out_name) {
+            info!("-> [Focus] Jumping to output {} for {:?}", out_name, win_id);
+            self.focused_output = Some(out_name.clone());
+            self.focused_tags = out_tags;
+        }
+        // 窗口不在显示器当前的标签上，切到它所在的标签 (沿用标签切换的动画方向)
+        let tags = if (out_tags & win_tags) == 0 {
+            self.perform_action(Action::FocusTag(win_tags));
+            win_tags
+        } else {
+            out_tags
+        };
+
+        self.focused_window = Some(win_id.clone());
+        self.tag_focus_history
+            .insert((out_name, tags), win_id.clone());
+
+        // 鼠标跟着焦点走，与跨屏切换一致
+        let geom = if is_floating {
+            Some(float_geo)
+        } else {
+            self.last_geometry.get(win_id).copied()
+        };
+        if let Some(g) = geom {
+            self.pending_pointer_warp = Some((g.x + g.w / 2, g.y + g.h / 2));
+        }
+
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+
+    /// 与带标记的窗口互换在树里的位置 (可以跨标签、跨显示器)，
+    /// 换过来的窗口占据原来的焦点位置并获得焦点
+    pub fn swap_with_mark(&mut self, win_id: &ObjectId, name: &str) {
+        let other_id = match self.marked_window(name) {
+            Some(id) if &id != win_id => id,
+            Some(_) => return,
+            None => {
+                warn!("-> [Mark] No window marked as {}", name);
+                return;
+            }
+        };
+        let tiled_key = |id: &ObjectId| {
+            self.windows
+                .iter()
+                .find(|w| &w.id == id && !w.is_floating && !w.is_minimized)
+                .and_then(|w| w.output.clone().map(|out| (out, w.tags)))
+        };
+        let (key_a, key_b) = match (tiled_key(win_id), tiled_key(&other_id)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => {
+                warn!("-> [Mark] Only tiled windows can be swapped");
+                return;
+            }
+        };
+
+        if key_a == key_b {
+            if let Some(root) = self.layout_roots.get_mut(&key_a) {
+                crate::wm::layout::LayoutNode::swap_windows(root, win_id, &other_id);
+            }
+        } else {
+            // 不同的树：两个窗口交换显示器和标签，再各自顶替对方的叶子
+            let mut data_a = None;
+            let mut data_b = None;
+            for w in self.windows.iter_mut() {
+                let target = if &w.id == win_id {
+                    &key_b
+                } else if w.id == other_id {
+                    &key_a
+                } else {
+                    continue;
+                };
+                w.output = Some(target.0.clone());
+                w.tags = target.1;
+                w.last_proposed_w = 0;
+                w.last_proposed_h = 0;
+                if &w.id == win_id {
+                    data_a = Some(w.clone());
+                } else {
+                    data_b = Some(w.clone());
+                }
+            }
+            if let (Some(a), Some(b)) = (data_a, data_b) {
+                if let Some(root) = self.layout_roots.get_mut(&key_a) {
+                    root.replace_window(win_id, b);
+                }
+                if let Some(root) = self.layout_roots.get_mut(&key_b) {
+                    root.replace_window(&other_id, a);
+                }
+            }
+            self.tag_focus_history
+                .retain(|_, id| id != win_id && *id != other_id);
+        }
+        info!(
+            "-> [Mark] Swapped {:?} with {} ({:?})",
+            win_id, name, other_id
+        );
+
+        if self.focused_window.as_ref() == Some(win_id) {
+            self.focused_window = Some(other_id.clone());
+            self.tag_focus_history.insert(key_a, other_id);
+        }
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+}
@@ -1,9 +1,18 @@
 pub mod actions;
 pub mod animation;
 pub mod binds;
+pub mod events;
+pub mod ipc;
 pub mod layout;
+pub mod marks;
+pub mod mru;
+pub mod presets;
+pub mod scratchpad;
+pub mod session;
+pub mod status;
+pub mod swallow;
 use self::actions::Action;
-use self::layout::{calculate_layout, Geometry, LayoutNode};
+use self::layout::{Gaps, Geometry, LayoutNode, Preselection, SizeHints, TagLayout};
 use crate::protocol::river_input::river_input_device_v1::{
     Event as InputDeviceEvent, RiverInputDeviceV1,
 };
@@ -110,6 +119,11 @@ pub struct WindowData {
     pub is_floating: bool,
     pub float_geo: Geometry,
     pub is_minimized: bool,
+    pub is_sticky: bool,   // 悬浮窗跟随所在显示器 (或焦点显示器) 当前的标签
+    pub is_terminal: bool, // 规则里 swallow="true"，从它启动的平铺窗口会顶替它
+    pub pid: Option<i32>,  // River 给的 (不可靠的) 进程号
+    // focus_mark / swap_with_mark 用的标记，同名标记只属于一个窗口
+    pub mark: Option<String>,
     pub anim_start_geo: Option<Geometry>,
     pub anim_target_geo: Option<Geometry>,
     pub current_visual_geo: Option<Geometry>,
@@ -141,6 +155,15 @@ pub struct RiverOutputInfo {
     pub h: i32,
 }
 
+/// 状态栏客户端：output 为 None 时跟随焦点显示器 (旧版 --waybar 行为)
+pub struct StatusClient {
+    pub stream: UnixStream,
+    pub output: Option<String>,
+    pub tag: Option<u32>, // 只推送某一个标签 (从 1 开始)，用于可点击的单标签按钮
+    pub format: crate::wm::status::StatusFormat,
+    pub last_sent: String,
+}
+
 pub struct AppState {
     pub config: crate::config::Config,
     pub needs_reload: bool,
@@ -164,14 +187,20 @@ pub struct AppState {
     pub device_names: HashMap<ObjectId, String>,
     pub ipc_listener: Option<UnixListener>,
     pub cmd_listener: Option<UnixListener>,
-    pub ipc_clients: Vec<UnixStream>,
+    pub ipc_clients: Vec<StatusClient>,
     pub output_manager: Option<ZwlrOutputManagerV1>,
     pub heads: Vec<HeadInfo>,
     pub last_output_serial: u32,
     pub layout_roots: HashMap<(String, u32), LayoutNode>,
+    pub tag_layouts: HashMap<(String, u32), TagLayout>, // 运行时切换过布局的标签，其余按配置默认
+    pub scroll_offsets: HashMap<(String, u32), i32>,    // scroller 布局每个标签的视口偏移
+    pub column_widths: HashMap<ObjectId, f32>, // scroller 布局里调整过宽度的列 (占屏幕比例)
+    pub grid_counts: HashMap<(String, u32), usize>, // grid 策略的标签上次重建网格时的窗口数
+    pub presel: Option<Preselection>,          // 手动预选，下一个插入到该窗口旁边的新窗口使用后清除
+    pub size_hints: HashMap<ObjectId, SizeHints>, // 客户端声明过最小/最大尺寸的窗口
+    pub gap_overrides: HashMap<(String, u32), Gaps>, // 运行时调整过间隙的标签
     pub focused_output: Option<String>,
     pub pending_pointer_warp: Option<(i32, i32)>,
-    pub last_sent_json: String,
     pub anonymous_ls_outputs: Vec<RiverLayerShellOutputV1>,
     pub wl_name_to_monitor_name: HashMap<u32, String>,
     pub active_river_outputs: Vec<RiverOutputInfo>,
@@ -187,9 +216,21 @@ pub struct AppState {
     pub pending_op_start: bool,
     pub pending_op_end: bool,
     pub minimized_slots: HashMap<String, wayland_backend::client::ObjectId>,
+    pub scratchpads: HashMap<String, ObjectId>, // scratchpad 名字 -> 认领的窗口
+    pub pending_scratchpads: HashMap<String, Instant>, // 已经启动、还在等窗口出现的 scratchpad
+    pub swallowed: HashMap<ObjectId, ObjectId>, // 顶替了终端的窗口 -> 被藏起来的终端
+    pub focus_mru: Vec<ObjectId>,               // 跨标签、跨显示器的焦点历史，最近的在前
+    pub mru_cycle: Option<crate::wm::mru::MruCycle>, // 进行中的 cycle_mru
     pub anim_start_time: Option<Instant>,
     pub tag_anim_direction: Option<crate::wm::layout::Direction>,
     pub tag_anim_old_mask: u32,
+    pub event_subscribers: Vec<crate::wm::events::EventSubscriber>,
+    pub last_event_snapshot: crate::wm::events::EventSnapshot,
+    pub session: Option<crate::wm::session::PendingSession>, // 启动后正在按状态文件还原的布局
+    pub last_session_dump: String, // 上一次写入状态文件的内容，没变化就不重复写
+    // 布局、标签或窗口状态变过，下一次 ManageStart 时写入状态文件
+    pub session_dirty: bool,
+    pub pending_presets: HashMap<(String, u32), crate::wm::presets::PendingPreset>, // 还有空位的布局预设
 }
 
 // --- 1. 监听 WlRegistry (寻找全局接口) ---
@@ -290,6 +331,10 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                         h: 0,
                     },
                     is_minimized: false,
+                    is_sticky: false,
+                    is_terminal: false,
+                    pid: None,
+                    mark: None,
                     anim_start_geo: None,
                     anim_target_geo: None,
                     current_visual_geo: None,
@@ -335,6 +380,7 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                     state.pointer_op_target = None;
                     state.pointer_op_initial_geo = None;
                     state.pending_op_end = false;
+                    state.session_dirty = true;
                 }
                 // ----------------------------------------
 
@@ -357,6 +403,8 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                         state.focused_tags = out_data.tags;
                     }
                 }
+                // sticky 窗口跟着标签走，之后的焦点恢复与显隐都按新的标签计算
+                state.follow_sticky_windows();
 
                 // 2. 智能焦点恢复
                 // 逻辑：如果当前没有焦点窗口，或者焦点窗口由于所在 Tag 被隐藏，则尝试恢复
@@ -646,6 +694,18 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                 // 3. 显隐控制：遍历所有窗口
                 let is_tag_animating = state.tag_anim_direction.is_some() && is_animating;
 
+                // grid 策略的标签窗口数变了就重建网格；
+                // tabbed/stacked 容器记住焦点所在的标签页，scroller 视口滚到焦点所在的列，
+                // 再算出被藏起来的窗口
+                state.rebuild_grids();
+                if let Some(f_id) = &state.focused_window {
+                    for root in state.layout_roots.values_mut() {
+                        root.remember_active(f_id);
+                    }
+                }
+                state.update_scroll_offsets();
+                let hidden_tiled = state.hidden_tiled_windows();
+
                 for w_data in &state.windows {
                     let is_visible = if let Some(win_out_id) = &w_data.output {
                         if let Some(out_data) = state.outputs.get(win_out_id) {
@@ -655,7 +715,9 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                             let match_old =
                                 is_tag_animating && (w_data.tags & state.tag_anim_old_mask) != 0;
 
-                            (match_current || match_old) && !w_data.is_minimized
+                            (match_current || match_old)
+                                && !w_data.is_minimized
+                                && !hidden_tiled.contains(&w_data.id)
                         } else {
                             false
                         }
@@ -707,13 +769,6 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                 let border_val = border_cfg
                     .and_then(|b| b.width.parse::<u32>().ok())
                     .unwrap_or(0);
-                let mut gaps_val = win_cfg
-                    .and_then(|c| c.gaps.as_ref())
-                    .and_then(|s| s.parse::<u32>().ok())
-                    .unwrap_or(0);
-                if gaps_val < border_val {
-                    gaps_val = border_val;
-                }
 
                 let normal_color_str = border_cfg.map(|b| b.color.as_str()).unwrap_or("#ffffff");
                 let resize_color_str = border_cfg
@@ -725,6 +780,11 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                     normal_color_str
                 };
                 let (br, bg, bb, ba) = Self::parse_color(target_color_str);
+                let presel_color = Self::parse_color(
+                    border_cfg
+                        .and_then(|b| b.presel_color.as_deref())
+                        .unwrap_or("#50fa7b"),
+                );
 
                 let is_smart = win_cfg
                     .map(|c| c.smart_borders.to_lowercase() == "true")
@@ -745,10 +805,15 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
 
                     for render_tag in active_trees {
                         let tree_key = (out_id.clone(), render_tag);
-                        if let Some(root) = state.layout_roots.get(&tree_key) {
-                            let mut results = Vec::new();
-                            calculate_layout(root, out_data.usable_area, &mut results);
-                            let window_count = results.len();
+                        if state.layout_roots.contains_key(&tree_key) {
+                            let results =
+                                state.arrange_tag_windows(&tree_key, out_data.usable_area);
+                            let gaps = state.gaps_for(&tree_key);
+                            // smart_borders 只看实际可见的窗口 (monocle/tabbed 只显示一个)
+                            let window_count = results
+                                .iter()
+                                .filter(|(window, _)| !hidden_tiled.contains(&window.id()))
+                                .count();
 
                             for (window, geom) in results {
                                 if let Some(w_data) =
@@ -765,52 +830,54 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                                         state.focused_window.as_ref() == Some(&window.id());
 
                                     // 计算四个方向的独立偏移量
-                                    let screen = out_data.usable_area;
-                                    let is_at_left = geom.x == screen.x;
-                                    let is_at_right = (geom.x + geom.w) == (screen.x + screen.w);
-                                    let is_at_top = geom.y == screen.y;
-                                    let is_at_bottom = (geom.y + geom.h) == (screen.y + screen.h);
-
                                     let (off_l, off_r, off_t, off_b) =
                                         if is_smart && window_count <= 1 {
                                             (0, 0, 0, 0)
                                         } else {
-                                            let half = (gaps_val as f32 / 2.0).ceil() as i32;
-                                            let full = gaps_val as i32;
-                                            (
-                                                if is_at_left { full } else { half },
-                                                if is_at_right { full } else { half },
-                                                if is_at_top { full } else { half },
-                                                if is_at_bottom { full } else { half },
-                                            )
+                                            gaps.offsets(geom, out_data.usable_area)
                                         };
 
-                                    // 设置边框
-                                    let current_border =
-                                        if is_focused && !(is_smart && window_count <= 1) {
-                                            border_val as i32
-                                        } else {
-                                            0
-                                        };
+                                    // 设置边框：有预选的窗口即使没有焦点也用预选颜色标出来
+                                    let is_presel = state
+                                        .presel
+                                        .as_ref()
+                                        .is_some_and(|p| p.window == window.id());
+                                    let current_border = if is_presel
+                                        || (is_focused && !(is_smart && window_count <= 1))
+                                    {
+                                        border_val as i32
+                                    } else {
+                                        0
+                                    };
+                                    let (r, g, b, a) = if is_presel {
+                                        presel_color
+                                    } else {
+                                        (br, bg, bb, ba)
+                                    };
                                     window.set_borders(
                                         crate::protocol::river_wm::river_window_v1::Edges::all(),
                                         current_border,
-                                        br,
-                                        bg,
-                                        bb,
-                                        ba,
+                                        r,
+                                        g,
+                                        b,
+                                        a,
                                     );
 
-                                    // 计算缩进后的目标几何体
-                                    let final_w = (geom.w - off_l - off_r).max(1);
-                                    let final_h = (geom.h - off_t - off_b).max(1);
-
-                                    let target_geo = crate::wm::layout::Geometry {
+                                    // 计算缩进后的目标几何体，超过最大尺寸的窗口在格子里居中
+                                    let hints = state
+                                        .size_hints
+                                        .get(&window.id())
+                                        .copied()
+                                        .unwrap_or_default();
+                                    let target_geo = hints.fit(crate::wm::layout::Geometry {
                                         x: geom.x + off_l,
                                         y: geom.y + off_t,
-                                        w: final_w,
-                                        h: final_h,
-                                    };
+                                        w: (geom.w - off_l - off_r).max(1),
+                                        h: (geom.h - off_t - off_b).max(1),
+                                    });
+                                    // 连最小尺寸都放不下时客户端会保持最小尺寸，按它实际会用的尺寸比较，避免反复重试
+                                    let final_w = target_geo.w.max(hints.min_w);
+                                    let final_h = target_geo.h.max(hints.min_h);
 
                                     // 动画滑动坐标算子
                                     let mut actual_start_geo = target_geo;
@@ -1024,6 +1091,10 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                         }
                     }
                 }
+                // 8. 记录焦点历史，对比状态快照向事件订阅者推送变化，并把布局写入状态文件
+                state.track_focus_mru();
+                state.emit_events();
+                state.save_session();
                 proxy.manage_finish();
             }
             WmEvent::RenderStart => {
@@ -1062,13 +1133,6 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                 let border_val = border_cfg
                     .and_then(|b| b.width.parse::<u32>().ok())
                     .unwrap_or(0);
-                let mut gaps_val = win_cfg
-                    .and_then(|c| c.gaps.as_ref())
-                    .and_then(|s| s.parse::<u32>().ok())
-                    .unwrap_or(0);
-                if gaps_val < border_val {
-                    gaps_val = border_val;
-                }
                 let is_smart = win_cfg
                     .map(|c| c.smart_borders.to_lowercase() == "true")
                     .unwrap_or(false);
@@ -1076,6 +1140,7 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                 let is_tag_animating = state.tag_anim_direction.is_some() && is_animating;
 
                 // 1. 渲染平铺层
+                let hidden_tiled = state.hidden_tiled_windows();
                 for (out_name, out_data) in &state.outputs {
                     let mut active_trees = vec![out_data.tags];
                     if is_tag_animating
@@ -1087,10 +1152,15 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
 
                     for render_tag in active_trees {
                         let tree_key = (out_name.clone(), render_tag);
-                        if let Some(root) = state.layout_roots.get(&tree_key) {
-                            let mut results = Vec::new();
-                            calculate_layout(root, out_data.usable_area, &mut results);
-                            let window_count = results.len();
+                        if state.layout_roots.contains_key(&tree_key) {
+                            let results =
+                                state.arrange_tag_windows(&tree_key, out_data.usable_area);
+                            let gaps = state.gaps_for(&tree_key);
+                            // smart_borders 只看实际可见的窗口 (monocle/tabbed 只显示一个)
+                            let window_count = results
+                                .iter()
+                                .filter(|(window, _)| !hidden_tiled.contains(&window.id()))
+                                .count();
 
                             for (window, geom) in results {
                                 if let Some(w_data) =
@@ -1106,34 +1176,24 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                                         w_data.node = Some(window.get_node(qh, ()));
                                     }
                                     if let Some(node) = &w_data.node {
-                                        let screen = out_data.usable_area;
-                                        let is_at_left = geom.x == screen.x;
-                                        let is_at_right =
-                                            (geom.x + geom.w) == (screen.x + screen.w);
-                                        let is_at_top = geom.y == screen.y;
-                                        let is_at_bottom =
-                                            (geom.y + geom.h) == (screen.y + screen.h);
-
                                         let (off_l, off_r, off_t, off_b) =
                                             if is_smart && window_count <= 1 {
                                                 (0, 0, 0, 0)
                                             } else {
-                                                let half = (gaps_val as f32 / 2.0).ceil() as i32;
-                                                let full = gaps_val as i32;
-                                                (
-                                                    if is_at_left { full } else { half },
-                                                    if is_at_right { full } else { half },
-                                                    if is_at_top { full } else { half },
-                                                    if is_at_bottom { full } else { half },
-                                                )
+                                                gaps.offsets(geom, out_data.usable_area)
                                             };
 
-                                        let target_geo = crate::wm::layout::Geometry {
+                                        let hints = state
+                                            .size_hints
+                                            .get(&window.id())
+                                            .copied()
+                                            .unwrap_or_default();
+                                        let target_geo = hints.fit(crate::wm::layout::Geometry {
                                             x: geom.x + off_l,
                                             y: geom.y + off_t,
                                             w: (geom.w - off_l - off_r).max(1),
                                             h: (geom.h - off_t - off_b).max(1),
-                                        };
+                                        });
 
                                         let mut actual_target_geo = target_geo;
                                         if is_tag_animating {
@@ -1169,7 +1229,11 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                                         w_data.current_visual_geo = Some(current_geo);
                                         node.set_position(current_geo.x, current_geo.y);
 
-                                        if is_animating {
+                                        // 动画中或 scroller 的列露出一半时，裁掉伸出可用区域的部分
+                                        let area = out_data.usable_area;
+                                        let overflows = current_geo.x < area.x
+                                            || current_geo.x + current_geo.w > area.x + area.w;
+                                        if is_animating || overflows {
                                             let (cx, cy, cw, ch) =
                                                 crate::wm::animation::calculate_clip_box(
                                                     current_geo,
@@ -1232,11 +1296,21 @@ impl Dispatch<RiverWindowManagerV1, ()> for AppState {
                     }
                 }
 
+                // 4. sticky 窗口 (画中画、视频通话) 始终压在最上面，包括切换标签的动画
+                for w_data in &state.windows {
+                    if w_data.is_sticky && w_data.is_floating && !w_data.is_minimized {
+                        if let Some(node) = &w_data.node {
+                            node.place_top();
+                        }
+                    }
+                }
+
                 proxy.render_finish();
             }
 
             WmEvent::Output { id } => {
                 info!("-> Found new physical output interface: {:?}", id.id());
+                state.session_dirty = true;
                 // 先初始化为 0，等待后续 Dimensions/Position 事件更新
                 state.active_river_outputs.push(RiverOutputInfo {
                     obj: id.clone(),
@@ -1309,6 +1383,7 @@ impl Dispatch<RiverOutputV1, ()> for AppState {
             state
                 .active_river_outputs
                 .retain(|i| i.obj.id() != proxy.id());
+            state.session_dirty = true;
         }
     }
 }
@@ -1491,8 +1566,19 @@ impl Dispatch<RiverWindowV1, ()> for AppState {
             // 当窗口被关闭（比如在终端里输了 exit）
             WinEvent::Closed => {
                 let id = proxy.id();
+                state.session_dirty = true;
                 // --- 【幽灵防线 (垃圾回收)】如果这个窗口在小黑屋里被意外杀死了，立刻释放它的插槽 ---
                 state.minimized_slots.retain(|_, v| *v != id);
+                state.scratchpads.retain(|_, v| *v != id);
+                state.swallowed.retain(|_, v| *v != id);
+                state.focus_mru.retain(|v| *v != id);
+                // 吞掉终端的窗口关闭时，终端回到原来的位置
+                state.release_swallowed(&id);
+                state.column_widths.remove(&id);
+                state.size_hints.remove(&id);
+                if state.presel.as_ref().is_some_and(|p| p.window == id) {
+                    state.presel = None;
+                }
                 if let Some(w_info) = state.windows.iter().find(|w| w.id == id) {
                     let win_tag = w_info.tags;
 
@@ -1571,6 +1657,7 @@ impl Dispatch<RiverWindowV1, ()> for AppState {
                     (None, 0)
                 };
                 state.clear_other_fullscreen(&id, &out_name, tags);
+                state.session_dirty = true;
                 // --------------------------
 
                 if let Some(w) = state.windows.iter_mut().find(|w| w.id == id) {
@@ -1585,6 +1672,7 @@ impl Dispatch<RiverWindowV1, ()> for AppState {
             WinEvent::ExitFullscreenRequested => {
                 let id = proxy.id();
                 info!("-> [Event] Window {:?} requested Exit Fullscreen", id);
+                state.session_dirty = true;
 
                 if let Some(w) = state.windows.iter_mut().find(|w| w.id == id) {
                     w.is_fullscreen = false;
@@ -1738,7 +1826,24 @@ impl Dispatch<RiverWindowV1, ()> for AppState {
                 if let Some(w) = state.windows.iter_mut().find(|w| w.id == id) {
                     w.is_fixed_size = is_fixed;
                 }
+                // 平铺布局把最小/最大尺寸当作约束，由相邻窗口吸收差额
+                let hints = SizeHints {
+                    min_w: min_width,
+                    min_h: min_height,
+                    max_w: max_width,
+                    max_h: max_height,
+                };
+                let changed = if hints == SizeHints::default() {
+                    state.size_hints.remove(&id).is_some()
+                } else {
+                    state.size_hints.insert(id.clone(), hints) != Some(hints)
+                };
                 state.apply_window_rules(&id);
+                if changed {
+                    if let Some(wm) = &state.river_wm {
+                        wm.manage_dirty();
+                    }
+                }
             }
 
             // --- 【处理父窗口事件 (Parent)】 ---
@@ -1750,6 +1855,14 @@ impl Dispatch<RiverWindowV1, ()> for AppState {
                 }
                 state.apply_window_rules(&id);
             }
+            // --- 【记录创建窗口的进程号，用于吞掉终端】 ---
+            WinEvent::UnreliablePid { unreliable_pid } => {
+                let id = proxy.id();
+                if let Some(w) = state.windows.iter_mut().find(|w| w.id == id) {
+                    w.pid = (unreliable_pid > 0).then_some(unreliable_pid);
+                }
+                state.apply_window_rules(&id);
+            }
             // --- 【捕获窗口标题】 ---
             WinEvent::Title { title } => {
                 let id = proxy.id();
@@ -1759,6 +1872,8 @@ impl Dispatch<RiverWindowV1, ()> for AppState {
                     w.title = title;
                 }
                 state.apply_window_rules(&id);
+                // 标题变化不一定触发 ManageStart，这里直接推送
+                state.emit_events();
             }
             _ => {}
         }
@@ -1795,36 +1910,9 @@ impl Dispatch<RiverXkbBindingV1, ()> for AppState {
                 .find(|b| b.obj.id() == proxy.id())
                 .map(|b| b.actions.clone());
 
-            // 现在 state 已经“自由”了，我们可以安全地调用 perform_action(&mut self)
+            // 现在 state 已经“自由”了，我们可以安全地调用 run_actions(&mut self)
             if let Some(actions) = actions_to_run {
-                for action in actions {
-                    state.perform_action(action.clone());
-
-                    if let Action::ReloadConfiguration = action {
-                        let serial = state.last_output_serial;
-                        state.apply_output_configs(qh, serial);
-                    }
-                }
-            }
-
-            // --- 核心重载逻辑 ---
-            if state.needs_reload {
-                info!("-> Perform shortcut hot reload...");
-                // 1. 销毁旧对象：告诉 River 别再监听这些按键了
-                // drain(..) 会清空数组并返回里面的元素
-                for kb in state.key_bindings.drain(..) {
-                    kb.obj.destroy();
-                }
-                // 2. 创建新对象：根据新 config 重新注册
-                self::binds::setup_keybindings(state, qh);
-                // 3. 强制通知：由于新绑定的 enable() 必须在 manage 序列执行
-                // 我们调用 manage_dirty() 强行让 River 发起一次 ManageStart
-                if let Some(wm) = &state.river_wm {
-                    // info!("-> MANAGE_DIRTY TRIGGERED BY:Dispatch<RiverXkbConfigV1>");
-                    wm.manage_dirty();
-                }
-                state.needs_reload = false;
-                info!("-> Hot reload completed!");
+                state.run_actions(actions, qh);
             }
         }
     }
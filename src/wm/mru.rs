@@ -0,0 +1,102 @@
+use crate::wm::AppState;
+use std::time::{Duration, Instant};
+use tracing::info;
+use wayland_backend::client::ObjectId;
+
+/// 进行中的 cycle_mru：期间 MRU 顺序保持不动，结束时选中的窗口才排到最前面
+pub struct MruCycle {
+    pub index: usize,
+    pub last_step: Instant,
+}
+
+impl AppState {
+    /// 停止连按 cycle_mru 多久之后确认选择 (毫秒)
+    fn mru_commit_delay(&self) -> Duration {
+        let ms = self
+            .config
+            .window
+            .as_ref()
+            .and_then(|w| w.mru_commit_delay.as_deref())
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(1000);
+        Duration::from_millis(ms)
+    }
+
+    fn mru_cycling(&self) -> bool {
+        self.mru_cycle
+            .as_ref()
+            .is_some_and(|c| c.last_step.elapsed() < self.mru_commit_delay())
+    }
+
+    /// ManageStart 里调用：把焦点窗口排到最前面 (循环进行中时不动)
+    pub fn track_focus_mru(&mut self) {
+        if self.mru_cycling() {
+            return;
+        }
+        self.mru_cycle = None;
+        if let Some(id) = self.focused_window.clone() {
+            if self.focus_mru.first() != Some(&id) {
+                self.focus_mru.retain(|v| *v != id);
+                self.focus_mru.insert(0, id);
+            }
+        }
+    }
+
+    /// 可以切换到的窗口：先按最近使用排序，从没聚焦过的窗口排在最后
+    fn mru_candidates(&self) -> Vec<ObjectId> {
+        let visible = |id: &ObjectId| {
+            self.windows
+                .iter()
+                .any(|w| &w.id == id && !w.is_minimized && w.output.is_some())
+        };
+        let mut list: Vec<ObjectId> = self
+            .focus_mru
+            .iter()
+            .filter(|id| visible(id))
+            .cloned()
+            .collect();
+        for w in &self.windows {
+            if !list.contains(&w.id) && visible(&w.id) {
+                list.push(w.id.clone());
+            }
+        }
+        list
+    }
+
+    /// 回到上一个聚焦的窗口，连按两次就在两个窗口之间来回切换
+    pub fn focus_last(&mut self) {
+        self.mru_cycle = None;
+        self.track_focus_mru();
+        if let Some(target) = self.mru_candidates().get(1).cloned() {
+            info!("-> [MRU] Back to {:?}", target);
+            self.focus_window(&target);
+        }
+    }
+
+    /// 按最近使用顺序循环切换窗口 (+1 更早的, -1 更近的)，需要时切换显示器和标签
+    pub fn cycle_mru(&mut self, delta: i32) {
+        let start = match &self.mru_cycle {
+            Some(c) if self.mru_cycling() => c.index,
+            _ => {
+                self.track_focus_mru();
+                0
+            }
+        };
+        let list = self.mru_candidates();
+        if list.len() < 2 {
+            return;
+        }
+        let index = (start as i32 + delta).rem_euclid(list.len() as i32) as usize;
+        self.mru_cycle = Some(MruCycle {
+            index,
+            last_step: Instant::now(),
+        });
+        info!(
+            "-> [MRU] Cycling to {:?} ({}/{})",
+            list[index],
+            index,
+            list.len()
+        );
+        self.focus_window(&list[index]);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::config::Config;
+use crate::wm::session::SavedNode;
+use crate::wm::AppState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::info;
+use wayland_backend::client::ObjectId;
+
+/// 命名的布局预设：一个标签的树形状与比例，每个窗口位置 (slot) 记下用来认领窗口的 app_id
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LayoutPreset {
+    pub slots: Vec<PresetSlot>,
+    pub root: SavedNode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PresetSlot {
+    pub app_id: Option<String>,
+}
+
+/// 已经加载、但还有空位的预设：之后在该标签打开的匹配窗口会填进去
+pub struct PendingPreset {
+    pub name: String,
+    pub preset: LayoutPreset,
+    pub filled: HashMap<usize, ObjectId>,
+}
+
+impl PresetSlot {
+    fn accepts(&self, app_id: Option<&str>) -> bool {
+        match (&self.app_id, app_id) {
+            (Some(want), Some(got)) => want.eq_ignore_ascii_case(got),
+            (None, _) => true,
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// 预设存放在配置目录下：~/.config/river/rrwm-layouts/<name>.json
+pub fn presets_dir() -> PathBuf {
+    Config::get_path().with_file_name("rrwm-layouts")
+}
+
+/// 预设名只允许字母、数字、- 和 _，避免写到目录外面
+pub fn preset_path(name: &str) -> Result<PathBuf, String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!(
+            "Invalid layout name {:?} (use letters, digits, - and _)",
+            name
+        ));
+    }
+    Ok(presets_dir().join(format!("{}.json", name)))
+}
+
+/// 列出所有已保存的预设名
+pub fn list_presets() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(presets_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    let path = e.path();
+                    let is_json = path.extension().is_some_and(|ext| ext == "json");
+                    let stem = path.file_stem()?.to_str()?.to_string();
+                    is_json.then_some(stem)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+impl AppState {
+    /// 焦点显示器当前显示的标签
+    fn focused_tree_key(&self) -> Option<(String, u32)> {
+        let out_name = self.focused_output.as_ref()?;
+        let tags = self.outputs.get(out_name)?.tags;
+        Some((out_name.clone(), tags))
+    }
+
+    /// 把当前标签的树保存为命名预设，失败原因交给调用者 (快捷键记日志，IPC 回给客户端)
+    pub fn save_layout(&mut self, name: &str) -> Result<(), String> {
+        let path = preset_path(name)?;
+        let root = self
+            .focused_tree_key()
+            .and_then(|key| self.layout_roots.get(&key))
+            .ok_or_else(|| format!("No tiled windows to save as {}", name))?;
+
+        let leaves = root.leaves();
+        let index_of: HashMap<ObjectId, usize> = leaves
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (w.id.clone(), i))
+            .collect();
+        let preset = LayoutPreset {
+            slots: leaves
+                .iter()
+                .map(|w| PresetSlot {
+                    app_id: w.app_id.clone(),
+                })
+                .collect(),
+            root: SavedNode::from_layout(root, &index_of)
+                .ok_or_else(|| format!("No tiled windows to save as {}", name))?,
+        };
+
+        serde_json::to_string_pretty(&preset)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                fs::create_dir_all(presets_dir())
+                    .and_then(|_| fs::write(&path, json))
+                    .map_err(|e| e.to_string())
+            })
+            .map_err(|e| format!("Failed to save {}: {}", name, e))?;
+        info!(
+            "-> [Layout] Saved {} windows as {} ({:?})",
+            preset.slots.len(),
+            name,
+            path
+        );
+        Ok(())
+    }
+
+    /// 把命名预设套用到当前标签：已有的窗口按 app_id 填进位置，剩下的位置留给之后打开的窗口
+    pub fn load_layout(&mut self, name: &str) -> Result<(), String> {
+        let path = preset_path(name)?;
+        let content =
+            fs::read_to_string(&path).map_err(|e| format!("Cannot read layout {}: {}", name, e))?;
+        let preset: LayoutPreset = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid layout {}: {}", name, e))?;
+        let key = self
+            .focused_tree_key()
+            .ok_or_else(|| "No focused output to load a layout on".to_string())?;
+
+        // 先按 app_id 精确认领，没写 app_id 的位置再接收剩下的窗口
+        let mut unplaced: Vec<(ObjectId, Option<String>)> = self
+            .layout_roots
+            .get(&key)
+            .map(|root| {
+                root.leaves()
+                    .iter()
+                    .map(|w| (w.id.clone(), w.app_id.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut filled = HashMap::new();
+        for exact in [true, false] {
+            for (i, slot) in preset.slots.iter().enumerate() {
+                if filled.contains_key(&i) || (exact && slot.app_id.is_none()) {
+                    continue;
+                }
+                if let Some(pos) = unplaced
+                    .iter()
+                    .position(|(_, app_id)| slot.accepts(app_id.as_deref()))
+                {
+                    filled.insert(i, unplaced.remove(pos).0);
+                }
+            }
+        }
+
+        if !filled.is_empty() {
+            self.apply_saved_tree(&key, &preset.root, &filled);
+        }
+        info!(
+            "-> [Layout] Loaded {} on {:?}: {}/{} slots filled",
+            name,
+            key,
+            filled.len(),
+            preset.slots.len()
+        );
+
+        if filled.len() < preset.slots.len() {
+            self.pending_presets.insert(
+                key,
+                PendingPreset {
+                    name: name.to_string(),
+                    preset,
+                    filled,
+                },
+            );
+        } else {
+            self.pending_presets.remove(&key);
+        }
+
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+        Ok(())
+    }
+
+    /// 新打开的平铺窗口如果匹配所在标签的预设空位，就填进去并按预设重建树
+    pub fn fill_preset_slot(&mut self, win_id: &ObjectId) {
+        let (key, app_id) = match self.windows.iter().find(|w| &w.id == win_id) {
+            Some(w) if !w.is_floating && !w.is_minimized && w.app_id.is_some() => match &w.output {
+                Some(out) => ((out.clone(), w.tags), w.app_id.clone()),
+                None => return,
+            },
+            _ => return,
+        };
+        let pending = match self.pending_presets.get_mut(&key) {
+            Some(p) => p,
+            None => return,
+        };
+        if pending.filled.values().any(|id| id == win_id) {
+            return;
+        }
+        let slot = match (0..pending.preset.slots.len()).find(|i| {
+            !pending.filled.contains_key(i) && pending.preset.slots[*i].accepts(app_id.as_deref())
+        }) {
+            Some(i) => i,
+            None => return,
+        };
+
+        pending.filled.insert(slot, win_id.clone());
+        info!(
+            "-> [Layout] {:?} fills slot {} of {}",
+            win_id, slot, pending.name
+        );
+        let root = pending.preset.root.clone();
+        let filled = pending.filled.clone();
+        if filled.len() == pending.preset.slots.len() {
+            self.pending_presets.remove(&key);
+        }
+        self.apply_saved_tree(&key, &root, &filled);
+    }
+}
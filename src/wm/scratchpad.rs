@@ -0,0 +1,235 @@
+use crate::config::ScratchpadConfig;
+use crate::wm::layout::{Geometry, LayoutNode};
+use crate::wm::AppState;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+use wayland_backend::client::ObjectId;
+
+/// 启动 scratchpad 之后等待它的窗口出现的时间，超时后再次切换会重新启动
+const SPAWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 与窗口规则相同的匹配方式：appid 不区分大小写地包含，title 为正则；两者至少写一个
+fn scratchpad_matches(cfg: &ScratchpadConfig, app_id: &str, title: &str) -> bool {
+    if cfg.appid.is_none() && cfg.title.is_none() {
+        return false;
+    }
+    let app_ok = cfg
+        .appid
+        .as_ref()
+        .is_none_or(|want| app_id.to_lowercase().contains(&want.to_lowercase()));
+    let title_ok = cfg.title.as_ref().is_none_or(|pattern| {
+        regex_lite::Regex::new(pattern)
+            .map(|re| re.is_match(title))
+            .unwrap_or(false)
+    });
+    app_ok && title_ok
+}
+
+impl AppState {
+    fn scratchpad_config(&self, name: &str) -> Option<ScratchpadConfig> {
+        self.config
+            .scratchpad
+            .as_ref()
+            .and_then(|pads| pads.get(name))
+            .cloned()
+    }
+
+    /// 显示/隐藏 scratchpad：不在当前标签就搬过来，已经显示且聚焦就藏起来，还没运行就启动它
+    pub fn toggle_scratchpad(&mut self, name: &str) {
+        let cfg = match self.scratchpad_config(name) {
+            Some(c) => c,
+            None => {
+                warn!("-> [Scratchpad] Unknown scratchpad {}", name);
+                return;
+            }
+        };
+
+        // 认领过的窗口已经关掉时，重新按 appid/title 找一个
+        let claimed = self
+            .scratchpads
+            .get(name)
+            .filter(|id| self.windows.iter().any(|w| &w.id == *id))
+            .cloned();
+        let win_id = match claimed.or_else(|| self.find_scratchpad_window(&cfg)) {
+            Some(id) => id,
+            None => {
+                self.spawn_scratchpad(name, &cfg);
+                return;
+            }
+        };
+        self.scratchpads.insert(name.to_string(), win_id.clone());
+
+        let visible = self.windows.iter().any(|w| {
+            w.id == win_id
+                && !w.is_minimized
+                && w.output.as_deref() == self.focused_output.as_deref()
+                && (w.tags & self.focused_tags) != 0
+        });
+        if !visible {
+            self.show_scratchpad(name, &cfg, &win_id);
+        } else if self.focused_window.as_ref() == Some(&win_id) {
+            self.hide_scratchpad(name, &win_id);
+        } else {
+            info!("-> [Scratchpad] Focusing {}", name);
+            self.focused_window = Some(win_id);
+        }
+
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+
+    /// 还没被任何 scratchpad 认领、也不在最小化插槽里的匹配窗口
+    fn find_scratchpad_window(&self, cfg: &ScratchpadConfig) -> Option<ObjectId> {
+        self.windows
+            .iter()
+            .filter(|w| !self.scratchpads.values().any(|id| *id == w.id))
+            .filter(|w| !self.minimized_slots.values().any(|id| *id == w.id))
+            .find(|w| {
+                scratchpad_matches(
+                    cfg,
+                    w.app_id.as_deref().unwrap_or_default(),
+                    w.title.as_deref().unwrap_or_default(),
+                )
+            })
+            .map(|w| w.id.clone())
+    }
+
+    fn spawn_scratchpad(&mut self, name: &str, cfg: &ScratchpadConfig) {
+        if self
+            .pending_scratchpads
+            .get(name)
+            .is_some_and(|started| started.elapsed() < SPAWN_TIMEOUT)
+        {
+            info!("-> [Scratchpad] {} is still starting", name);
+            return;
+        }
+        let cmd = match cfg.cmd.as_deref() {
+            Some(c) if !c.is_empty() => c,
+            _ => {
+                warn!("-> [Scratchpad] {} is not running and has no cmd", name);
+                return;
+            }
+        };
+        info!("-> [Scratchpad] Starting {}: {}", name, cmd);
+        match std::process::Command::new("sh").arg("-c").arg(cmd).spawn() {
+            Ok(_) => {
+                self.pending_scratchpads
+                    .insert(name.to_string(), Instant::now());
+            }
+            Err(e) => error!("-> [Scratchpad] Failed to start {}: {}", name, e),
+        }
+    }
+
+    /// 新窗口出现时，如果是某个刚启动的 scratchpad 的窗口，就认领它并显示出来
+    pub fn claim_scratchpad(&mut self, win_id: &ObjectId) -> bool {
+        self.pending_scratchpads
+            .retain(|_, started| started.elapsed() < SPAWN_TIMEOUT);
+        if self.scratchpads.values().any(|id| id == win_id) {
+            return true;
+        }
+        let (app_id, title) = match self.windows.iter().find(|w| &w.id == win_id) {
+            Some(w) => (w.app_id.clone(), w.title.clone()),
+            None => return false,
+        };
+        let found = self.pending_scratchpads.keys().find_map(|name| {
+            let cfg = self.scratchpad_config(name)?;
+            scratchpad_matches(
+                &cfg,
+                app_id.as_deref().unwrap_or_default(),
+                title.as_deref().unwrap_or_default(),
+            )
+            .then(|| (name.clone(), cfg))
+        });
+        let (name, cfg) = match found {
+            Some(f) => f,
+            None => return false,
+        };
+
+        self.pending_scratchpads.remove(&name);
+        self.scratchpads.insert(name.clone(), win_id.clone());
+        self.show_scratchpad(&name, &cfg, win_id);
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+        true
+    }
+
+    /// 把 scratchpad 作为悬浮窗放到焦点显示器的当前标签上，并聚焦它
+    fn show_scratchpad(&mut self, name: &str, cfg: &ScratchpadConfig, win_id: &ObjectId) {
+        let out_name = match self.focused_output.clone() {
+            Some(o) => o,
+            None => return,
+        };
+        let tags = self.focused_tags;
+        let area = match self.outputs.get(&out_name) {
+            Some(o) => o.usable_area,
+            None => return,
+        };
+
+        // 平铺着的窗口 (例如 appid 来得比规则晚) 先从树里拿出来
+        let tiled_key = self
+            .windows
+            .iter()
+            .find(|w| &w.id == win_id && !w.is_floating && !w.is_minimized)
+            .and_then(|w| w.output.clone().map(|out| (out, w.tags)));
+        if let Some(key) = tiled_key {
+            if let Some(root) = self.layout_roots.remove(&key) {
+                if let Some(new_root) = LayoutNode::remove_at(root, win_id) {
+                    self.layout_roots.insert(key, new_root);
+                }
+            }
+        }
+        self.minimized_slots.retain(|_, id| id != win_id);
+
+        let w = (Self::parse_dimension_ratio(cfg.width.as_deref().unwrap_or("80%"), area.w)
+            * area.w as f32) as i32;
+        let h = (Self::parse_dimension_ratio(cfg.height.as_deref().unwrap_or("80%"), area.h)
+            * area.h as f32) as i32;
+        let (w, h) = (w.min(area.w).max(1), h.min(area.h).max(1));
+        let y = match cfg.position.as_deref().map(|p| p.to_lowercase()).as_deref() {
+            Some("top") => area.y,
+            Some("bottom") => area.y + area.h - h,
+            _ => area.y + (area.h - h) / 2,
+        };
+        let geo = Geometry {
+            x: area.x + (area.w - w) / 2,
+            y,
+            w,
+            h,
+        };
+
+        if let Some(win) = self.windows.iter_mut().find(|w| &w.id == win_id) {
+            win.is_floating = true;
+            win.is_minimized = false;
+            win.is_fullscreen = false;
+            win.output = Some(out_name.clone());
+            win.tags = tags;
+            win.float_geo = geo;
+            // 强制重置，确保 ManageStart 能发送一次 propose_dimensions
+            win.last_proposed_w = 0;
+            win.last_proposed_h = 0;
+        }
+        info!(
+            "-> [Scratchpad] Showing {} on {} ({:b})",
+            name, out_name, tags
+        );
+
+        self.focused_window = Some(win_id.clone());
+        self.tag_focus_history
+            .insert((out_name, tags), win_id.clone());
+    }
+
+    /// 藏起 scratchpad，焦点交给 ManageStart 在同一层级里找接班人
+    fn hide_scratchpad(&mut self, name: &str, win_id: &ObjectId) {
+        info!("-> [Scratchpad] Hiding {}", name);
+        if let Some(w) = self.windows.iter_mut().find(|w| &w.id == win_id) {
+            w.is_minimized = true;
+        }
+        self.tag_focus_history.retain(|_, id| id != win_id);
+        if self.focused_window.as_ref() == Some(win_id) {
+            self.focused_window = None;
+            self.restrict_focus_to_floating = true;
+        }
+    }
+}
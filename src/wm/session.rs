@@ -0,0 +1,543 @@
+use crate::wm::layout::{ContainerMode, Geometry, LayoutNode, SplitType, TagLayout};
+use crate::wm::AppState;
+use crate::wm::WindowData;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+use wayland_backend::client::ObjectId;
+
+/// 启动后多久之内出现的窗口会按状态文件还原，过了这段时间才开始覆盖写入新的状态
+const RESTORE_GRACE: Duration = Duration::from_secs(15);
+
+/// 写入状态文件的整个桌面布局，窗口通过 app_id/title 重新认领
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SessionFile {
+    pub windows: Vec<SavedWindow>,
+    pub trees: Vec<SavedTree>,
+    pub layouts: Vec<SavedLayout>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedWindow {
+    pub app_id: String,
+    pub title: Option<String>,
+    pub output: Option<String>,
+    pub tags: u32,
+    pub floating: bool,
+    pub float_geo: Geometry,
+    pub fullscreen: bool,
+    pub minimized_slot: Option<String>,
+    #[serde(default)]
+    pub scratchpad: Option<String>, // 认领它的 scratchpad
+    #[serde(default)]
+    pub hidden: bool, // scratchpad 是否处于隐藏状态
+    #[serde(default)]
+    pub sticky: bool,
+    #[serde(default)]
+    pub terminal: bool,
+    #[serde(default)]
+    pub swallowed_by: Option<usize>, // 顶替了这个终端的窗口 (windows 里的下标)
+    #[serde(default)]
+    pub mark: Option<String>,
+}
+
+/// 某个 (显示器, 标签) 的树，focused 为该标签记住的焦点 (windows 里的下标)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedTree {
+    pub output: String,
+    pub tags: u32,
+    pub focused: Option<usize>,
+    pub root: SavedNode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SavedNode {
+    Window {
+        index: usize,
+    },
+    Container {
+        split: SplitType,
+        ratio: f32,
+        mode: ContainerMode,
+        left: Box<SavedNode>,
+        right: Box<SavedNode>,
+    },
+}
+
+/// 运行时切换过布局的标签
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedLayout {
+    pub output: String,
+    pub tags: u32,
+    pub layout: TagLayout,
+}
+
+/// 正在还原中的状态：claimed 记录已经认领的窗口 (windows 下标 -> 实际窗口)
+pub struct PendingSession {
+    pub file: SessionFile,
+    pub claimed: HashMap<usize, ObjectId>,
+    pub deadline: Instant,
+}
+
+/// 状态文件路径：优先 $XDG_RUNTIME_DIR，按 WAYLAND_DISPLAY 区分不同的 River 实例
+pub fn session_path() -> PathBuf {
+    let display_name = std::env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".to_string());
+    let dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(dir).join(format!("rrwm-{}-state.json", display_name))
+}
+
+impl SavedNode {
+    pub fn from_layout(node: &LayoutNode, index_of: &HashMap<ObjectId, usize>) -> Option<Self> {
+        match node {
+            LayoutNode::Window(w) => index_of
+                .get(&w.id)
+                .map(|&index| SavedNode::Window { index }),
+            LayoutNode::Container {
+                split_type,
+                ratio,
+                left_child,
+                right_child,
+                mode,
+                ..
+            } => {
+                let left = Self::from_layout(left_child, index_of);
+                let right = Self::from_layout(right_child, index_of);
+                match (left, right) {
+                    (Some(l), Some(r)) => Some(SavedNode::Container {
+                        split: *split_type,
+                        ratio: *ratio,
+                        mode: *mode,
+                        left: Box::new(l),
+                        right: Box::new(r),
+                    }),
+                    (l, r) => l.or(r),
+                }
+            }
+        }
+    }
+
+    /// 用已经认领的窗口重建子树，还没出现的窗口所在的分支会被收起
+    pub fn to_layout(
+        &self,
+        claimed: &HashMap<usize, ObjectId>,
+        windows: &[WindowData],
+    ) -> Option<LayoutNode> {
+        match self {
+            SavedNode::Window { index } => {
+                let id = claimed.get(index)?;
+                windows
+                    .iter()
+                    .find(|w| &w.id == id)
+                    .map(|w| LayoutNode::Window(w.clone()))
+            }
+            SavedNode::Container {
+                split,
+                ratio,
+                mode,
+                left,
+                right,
+            } => {
+                let left = left.to_layout(claimed, windows);
+                let right = right.to_layout(claimed, windows);
+                match (left, right) {
+                    (Some(l), Some(r)) => {
+                        let mut node = LayoutNode::split(*split, *ratio, l, r);
+                        if let LayoutNode::Container { mode: m, .. } = &mut node {
+                            *m = *mode;
+                        }
+                        Some(node)
+                    }
+                    (l, r) => l.or(r),
+                }
+            }
+        }
+    }
+
+    fn contains(&self, target: usize) -> bool {
+        match self {
+            SavedNode::Window { index } => *index == target,
+            SavedNode::Container { left, right, .. } => {
+                left.contains(target) || right.contains(target)
+            }
+        }
+    }
+}
+
+impl AppState {
+    /// 启动时读取上一次的状态文件，之后出现的窗口按它还原
+    pub fn load_session(&mut self) {
+        let path = session_path();
+        let content = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(_) => return,
+        };
+        let file: SessionFile = match serde_json::from_str(&content) {
+            Ok(f) => f,
+            Err(e) => {
+                warn!(
+                    "-> [Session] Ignoring unreadable state file {:?}: {}",
+                    path, e
+                );
+                return;
+            }
+        };
+
+        info!(
+            "-> [Session] Loaded {} windows and {} trees from {:?}",
+            file.windows.len(),
+            file.trees.len(),
+            path
+        );
+        for saved in &file.layouts {
+            self.tag_layouts
+                .insert((saved.output.clone(), saved.tags), saved.layout);
+        }
+        if !file.windows.is_empty() {
+            self.session = Some(PendingSession {
+                file,
+                claimed: HashMap::new(),
+                deadline: Instant::now() + RESTORE_GRACE,
+            });
+        }
+    }
+
+    /// 尝试用状态文件认领这个窗口，成功 (或早已认领) 时返回 true，调用方不再套用窗口规则
+    pub fn restore_window(&mut self, win_id: &ObjectId) -> bool {
+        let session = match self.session.as_ref() {
+            Some(s) if s.deadline > Instant::now() => s,
+            Some(_) => {
+                info!("-> [Session] Restore window expired");
+                self.finish_restore();
+                return false;
+            }
+            None => return false,
+        };
+        if session.claimed.values().any(|id| id == win_id) {
+            return true;
+        }
+
+        let (app_id, title) = match self.windows.iter().find(|w| &w.id == win_id) {
+            Some(w) => match &w.app_id {
+                Some(app_id) if !app_id.is_empty() => (app_id.clone(), w.title.clone()),
+                _ => return false,
+            },
+            None => return false,
+        };
+
+        // 同一个 app_id 有多个窗口时优先标题相同的那个
+        let candidates: Vec<usize> = session
+            .file
+            .windows
+            .iter()
+            .enumerate()
+            .filter(|(i, s)| s.app_id == app_id && !session.claimed.contains_key(i))
+            .map(|(i, _)| i)
+            .collect();
+        let index = match candidates
+            .iter()
+            .find(|&&i| title.is_some() && session.file.windows[i].title == title)
+            .or(candidates.first())
+        {
+            Some(&i) => i,
+            None => return false,
+        };
+        let saved = session.file.windows[index].clone();
+        info!(
+            "-> [Session] Restoring {:?} as {} ({:?})",
+            win_id, app_id, saved.title
+        );
+
+        // 窗口规则可能已经把它放进了某棵树，先拿出来
+        let keys: Vec<(String, u32)> = self.layout_roots.keys().cloned().collect();
+        for key in keys {
+            if let Some(root) = self.layout_roots.remove(&key) {
+                if let Some(new_root) = LayoutNode::remove_at(root, win_id) {
+                    self.layout_roots.insert(key, new_root);
+                }
+            }
+        }
+
+        let output = saved
+            .output
+            .clone()
+            .filter(|name| self.outputs.contains_key(name));
+        if let Some(w) = self.windows.iter_mut().find(|w| &w.id == win_id) {
+            if output.is_some() {
+                w.output = output;
+            }
+            w.tags = saved.tags;
+            w.is_floating = saved.floating;
+            w.float_geo = saved.float_geo;
+            w.is_fullscreen = saved.fullscreen;
+            w.is_sticky = saved.sticky && saved.floating;
+            w.is_terminal = saved.terminal;
+            if saved.mark.is_some() {
+                w.mark = saved.mark.clone();
+            }
+            // 被吞掉的终端先藏着，等顶替它的窗口也回来
+            if saved.swallowed_by.is_some() {
+                w.is_minimized = true;
+            }
+            w.last_proposed_w = 0;
+            w.last_proposed_h = 0;
+        }
+        if let Some(slot) = &saved.minimized_slot {
+            if !self.minimized_slots.contains_key(slot) {
+                if let Some(w) = self.windows.iter_mut().find(|w| &w.id == win_id) {
+                    w.is_minimized = true;
+                }
+                self.minimized_slots.insert(slot.clone(), win_id.clone());
+            }
+        }
+        if let Some(name) = &saved.scratchpad {
+            if !self.scratchpads.contains_key(name) {
+                if let Some(w) = self.windows.iter_mut().find(|w| &w.id == win_id) {
+                    w.is_minimized = saved.hidden;
+                }
+                self.scratchpads.insert(name.clone(), win_id.clone());
+            }
+        }
+
+        if let Some(session) = self.session.as_mut() {
+            session.claimed.insert(index, win_id.clone());
+        }
+        self.relink_swallowed(index);
+        let is_tiled = !saved.floating
+            && self
+                .windows
+                .iter()
+                .any(|w| &w.id == win_id && !w.is_minimized);
+        if is_tiled {
+            self.rebuild_saved_tree(index);
+        }
+
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+        true
+    }
+
+    /// 被吞掉的终端和顶替它的窗口都认领回来之后，重新记下两者的关系
+    fn relink_swallowed(&mut self, index: usize) {
+        let session = match self.session.as_ref() {
+            Some(s) => s,
+            None => return,
+        };
+        let pairs: Vec<(ObjectId, ObjectId)> = session
+            .file
+            .windows
+            .iter()
+            .enumerate()
+            .filter_map(|(term, s)| Some((s.swallowed_by?, term)))
+            .filter(|&(child, term)| child == index || term == index)
+            .filter_map(|(child, term)| {
+                Some((
+                    session.claimed.get(&child)?.clone(),
+                    session.claimed.get(&term)?.clone(),
+                ))
+            })
+            .collect();
+        for (child, term) in pairs {
+            info!("-> [Session] {:?} still swallows {:?}", child, term);
+            self.swallowed.insert(child, term);
+        }
+    }
+
+    /// 结束还原：顶替终端的窗口没有回来时，把藏着的终端放回它所在的标签
+    fn finish_restore(&mut self) {
+        let session = match self.session.take() {
+            Some(s) => s,
+            None => return,
+        };
+        let orphans: Vec<ObjectId> = session
+            .file
+            .windows
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| {
+                s.swallowed_by
+                    .is_some_and(|child| !session.claimed.contains_key(&child))
+            })
+            .filter_map(|(term, _)| session.claimed.get(&term).cloned())
+            .collect();
+        for term_id in orphans {
+            let key = match self.windows.iter().find(|w| w.id == term_id) {
+                Some(w) => match &w.output {
+                    Some(out) => (out.clone(), w.tags),
+                    None => continue,
+                },
+                None => continue,
+            };
+            self.unhide_terminal(&term_id, key, None);
+        }
+    }
+
+    /// 按状态文件重建包含该窗口的树，不在状态文件里的窗口重新插到旁边
+    fn rebuild_saved_tree(&mut self, index: usize) {
+        let session = match self.session.as_ref() {
+            Some(s) => s,
+            None => return,
+        };
+        let tree = match session.file.trees.iter().find(|t| t.root.contains(index)) {
+            Some(t) => t,
+            None => return,
+        };
+        let key = (tree.output.clone(), tree.tags);
+        let focused = tree.focused.and_then(|i| session.claimed.get(&i)).cloned();
+        let root = tree.root.clone();
+        let claimed = session.claimed.clone();
+
+        if self.apply_saved_tree(&key, &root, &claimed) {
+            if let Some(f_id) = focused {
+                self.tag_focus_history.insert(key, f_id);
+            }
+        }
+    }
+
+    /// 按保存的形状重建某个标签的树 (slots: 形状里的下标 -> 实际窗口)，
+    /// 树里原有但不在形状中的窗口重新插到旁边；一个窗口都对不上时返回 false
+    pub fn apply_saved_tree(
+        &mut self,
+        key: &(String, u32),
+        root: &SavedNode,
+        slots: &HashMap<usize, ObjectId>,
+    ) -> bool {
+        let rebuilt = match root.to_layout(slots, &self.windows) {
+            Some(root) => root,
+            None => return false,
+        };
+
+        let placed: Vec<ObjectId> = rebuilt.leaves().iter().map(|w| w.id.clone()).collect();
+        let extras: Vec<WindowData> = self
+            .layout_roots
+            .remove(key)
+            .map(|old| {
+                old.leaves()
+                    .into_iter()
+                    .filter(|w| !placed.contains(&w.id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.layout_roots.insert(key.clone(), rebuilt);
+        for extra in extras {
+            self.insert_tiled(key, &placed[0], extra, None);
+        }
+        true
+    }
+
+    /// 状态有变化时写入状态文件 (还原期间不写，免得把上一次的状态覆盖掉)
+    pub fn save_session(&mut self) {
+        if let Some(session) = &self.session {
+            if session.deadline > Instant::now() {
+                return;
+            }
+            info!("-> [Session] Restore window over, resuming state file updates");
+            self.finish_restore();
+            self.session_dirty = true;
+        }
+        // 只在有实际改动时才生成状态；标题变化不算改动，随下一次保存一起写入
+        if !self.session_dirty {
+            return;
+        }
+        self.session_dirty = false;
+
+        let managed: Vec<&WindowData> =
+            self.windows.iter().filter(|w| w.app_id.is_some()).collect();
+        let index_of: HashMap<ObjectId, usize> = managed
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (w.id.clone(), i))
+            .collect();
+
+        let windows = managed
+            .iter()
+            .map(|w| SavedWindow {
+                app_id: w.app_id.clone().unwrap_or_default(),
+                title: w.title.clone(),
+                output: w.output.clone(),
+                tags: w.tags,
+                floating: w.is_floating,
+                float_geo: w.float_geo,
+                fullscreen: w.is_fullscreen,
+                minimized_slot: self
+                    .minimized_slots
+                    .iter()
+                    .find(|(_, id)| **id == w.id)
+                    .map(|(slot, _)| slot.clone()),
+                scratchpad: self
+                    .scratchpads
+                    .iter()
+                    .find(|(_, id)| **id == w.id)
+                    .map(|(name, _)| name.clone()),
+                hidden: w.is_minimized,
+                sticky: w.is_sticky,
+                terminal: w.is_terminal,
+                swallowed_by: self
+                    .swallowed
+                    .iter()
+                    .find(|(_, term)| **term == w.id)
+                    .and_then(|(child, _)| index_of.get(child))
+                    .copied(),
+                mark: w.mark.clone(),
+            })
+            .collect();
+
+        let mut trees: Vec<SavedTree> = self
+            .layout_roots
+            .iter()
+            .filter_map(|(key, root)| {
+                Some(SavedTree {
+                    output: key.0.clone(),
+                    tags: key.1,
+                    focused: self
+                        .tag_focus_history
+                        .get(key)
+                        .and_then(|id| index_of.get(id))
+                        .copied(),
+                    root: SavedNode::from_layout(root, &index_of)?,
+                })
+            })
+            .collect();
+        trees.sort_by(|a, b| (&a.output, a.tags).cmp(&(&b.output, b.tags)));
+
+        let mut layouts: Vec<SavedLayout> = self
+            .tag_layouts
+            .iter()
+            .map(|(key, layout)| SavedLayout {
+                output: key.0.clone(),
+                tags: key.1,
+                layout: *layout,
+            })
+            .collect();
+        layouts.sort_by(|a, b| (&a.output, a.tags).cmp(&(&b.output, b.tags)));
+
+        let file = SessionFile {
+            windows,
+            trees,
+            layouts,
+        };
+        let dump = match serde_json::to_string_pretty(&file) {
+            Ok(d) => d,
+            Err(e) => {
+                error!("-> [Session] Failed to serialize state: {}", e);
+                return;
+            }
+        };
+        if dump == self.last_session_dump {
+            return;
+        }
+
+        // 先写临时文件再改名，避免崩溃时留下半个文件
+        let path = session_path();
+        let tmp = path.with_extension("json.tmp");
+        match fs::write(&tmp, &dump).and_then(|_| fs::rename(&tmp, &path)) {
+            Ok(_) => self.last_session_dump = dump,
+            Err(e) => error!("-> [Session] Failed to write {:?}: {}", path, e),
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use crate::wm::actions::WaybarResponse;
+use crate::wm::layout::{ContainerMode, LayoutMode};
+use crate::wm::AppState;
+use crate::wm::WindowData;
+use serde::Serialize;
+use wayland_backend::client::ObjectId;
+
+/// 状态栏输出格式，由客户端 --format 选择
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatusFormat {
+    Waybar, // {"text", "tooltip", "class"}
+    I3bar,  // i3bar 协议的一行 block 数组 (头部由客户端输出)
+    Eww,    // 结构化 JSON，方便 eww deflisten / yambar 脚本取字段
+    Plain,  // 纯文本：渲染后的 [status] format 模板
+}
+
+impl StatusFormat {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "waybar" => Ok(StatusFormat::Waybar),
+            "i3bar" => Ok(StatusFormat::I3bar),
+            "eww" | "json" => Ok(StatusFormat::Eww),
+            "plain" | "text" => Ok(StatusFormat::Plain),
+            _ => Err(format!(
+                "Unknown status format {} (expected waybar, i3bar, eww or plain)",
+                name
+            )),
+        }
+    }
+}
+
+/// 单个标签的状态，text 为套用模板后的结果
+#[derive(Serialize, Debug, Clone)]
+pub struct TagStatus {
+    pub index: u32,
+    pub icon: String,
+    pub focused: bool,
+    pub occupied: bool,
+    pub count: usize,
+    pub text: String,
+}
+
+/// 某个显示器 (或跟随焦点) 的完整状态，eww 格式直接序列化它
+#[derive(Serialize, Debug, Clone)]
+pub struct StatusSnapshot {
+    pub output: Option<String>,
+    pub tags: Vec<TagStatus>,
+    pub app_id: String,
+    pub title: String,
+    pub mode: String,
+    pub layout: String, // 例如 "tree"、"master_stack"、"monocle 2/3"、"tabbed 1/4"
+    pub text: String,
+    pub tooltip: String,
+}
+
+#[derive(Serialize)]
+struct I3barBlock {
+    name: &'static str,
+    instance: String,
+    full_text: String,
+    markup: &'static str,
+}
+
+impl From<TagStatus> for I3barBlock {
+    fn from(t: TagStatus) -> Self {
+        I3barBlock {
+            name: "rrwm_tag",
+            instance: t.index.to_string(),
+            full_text: t.text,
+            markup: "pango",
+        }
+    }
+}
+
+/// 渲染单个标签：超出显示范围的标签输出空内容，Waybar 会自动隐藏该模块
+fn render_tag_status(status: &StatusSnapshot, index: u32, format: StatusFormat) -> String {
+    let tag = status.tags.iter().find(|t| t.index == index);
+    match format {
+        StatusFormat::Waybar => {
+            let class = match tag {
+                Some(t) if t.focused => "focused",
+                Some(t) if t.occupied => "occupied",
+                Some(_) => "empty",
+                None => "hidden",
+            };
+            serde_json::to_string(&WaybarResponse {
+                text: tag.map(|t| t.text.clone()).unwrap_or_default(),
+                tooltip: status.tooltip.clone(),
+                class: class.to_string(),
+            })
+            .unwrap_or_default()
+        }
+        StatusFormat::I3bar => {
+            let blocks: Vec<I3barBlock> = tag.cloned().map(I3barBlock::from).into_iter().collect();
+            serde_json::to_string(&blocks).unwrap_or_default()
+        }
+        StatusFormat::Eww => serde_json::to_string(&tag).unwrap_or_default(),
+        StatusFormat::Plain => tag.map(|t| t.text.clone()).unwrap_or_default(),
+    }
+}
+
+/// 替换模板里的占位符：{index} {icon} {count} {title} {app_id} {mode} {tags} {layout}
+/// 从左到右只扫描一遍，替换进去的值 (例如标题里的 "{tags}") 不会再被展开
+fn fill_template(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let key = &after[..end];
+            vars.iter().find(|(k, _)| *k == key).map(|(_, v)| (*v, end))
+        });
+        match value {
+            Some((v, end)) => {
+                result.push_str(v);
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Waybar / i3bar 按 Pango markup 解析文本，标题和 app_id 里的特殊字符必须转义
+fn escape_markup(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl AppState {
+    /// 按客户端要求的显示器与格式渲染一行状态；指定 tag 时只渲染这一个标签 (可点击的单标签按钮)
+    pub fn render_status(
+        &self,
+        output: Option<&str>,
+        tag: Option<u32>,
+        format: StatusFormat,
+    ) -> String {
+        let status = self.status_snapshot(output, format);
+        if let Some(index) = tag {
+            return render_tag_status(&status, index, format);
+        }
+        match format {
+            StatusFormat::Waybar => serde_json::to_string(&WaybarResponse {
+                text: status.text,
+                tooltip: status.tooltip,
+                class: "rrwm-status".to_string(),
+            })
+            .unwrap_or_default(),
+            StatusFormat::I3bar => {
+                let blocks: Vec<I3barBlock> =
+                    status.tags.into_iter().map(I3barBlock::from).collect();
+                serde_json::to_string(&blocks).unwrap_or_default()
+            }
+            StatusFormat::Eww => serde_json::to_string(&status).unwrap_or_default(),
+            StatusFormat::Plain => status.text,
+        }
+    }
+
+    /// output 为 None 时沿用旧行为：跟随焦点显示器，并统计所有显示器的窗口
+    pub fn status_snapshot(&self, output: Option<&str>, format: StatusFormat) -> StatusSnapshot {
+        let out_name = output.or(self.focused_output.as_deref());
+        let (occupied, focused_tags) = match output {
+            Some(name) => (
+                self.get_occupied_tags_for_monitor(name),
+                self.outputs.get(name).map(|o| o.tags).unwrap_or(0),
+            ),
+            None => (self.get_occupied_tags(), self.focused_tags),
+        };
+        let waybar_cfg = self.config.waybar.as_ref();
+        let status_cfg = self.config.status.as_ref();
+
+        let current = self.get_output_window(output);
+        let app_id = current
+            .map(|w| w.app_id.clone().unwrap_or_else(|| "Unknown".to_string()))
+            .unwrap_or_default();
+        let title = current.and_then(|w| w.title.clone()).unwrap_or_default();
+        let mode = if self.is_resize_mode {
+            "resize"
+        } else {
+            "normal"
+        };
+        let layout = self.layout_indicator(out_name, focused_tags, current);
+        // 填进模板的标题与 app_id：Pango markup 格式需要转义，eww / plain 保持原样
+        let (markup_title, markup_app_id) = match format {
+            StatusFormat::Waybar | StatusFormat::I3bar => {
+                (escape_markup(&title), escape_markup(&app_id))
+            }
+            StatusFormat::Eww | StatusFormat::Plain => (title.clone(), app_id.clone()),
+        };
+
+        let mut tags = Vec::new();
+
+        // 1. 计算显示范围
+        let max_occupied_idx = if occupied == 0 {
+            0
+        } else {
+            32 - occupied.leading_zeros() - 1
+        };
+        let focused_idx = if focused_tags == 0 {
+            0
+        } else {
+            32 - focused_tags.leading_zeros() - 1
+        };
+        let visual_bound = (max_occupied_idx.max(focused_idx) + 1).min(31);
+
+        // 2. 循环生成每个标签的样式
+        for i in 0..=visual_bound {
+            let mask = 1 << i;
+
+            // --- 优先尝试获取动态图标 (以前端展示的显示器为准) ---
+            let mut icon = out_name.and_then(|name| self.get_dynamic_icon(name, i));
+
+            // 如果没有动态规则匹配，回退到 [waybar] tag_icons
+            if icon.is_none() {
+                icon = waybar_cfg
+                    .and_then(|c| c.tag_icons.as_ref())
+                    .and_then(|icons| icons.get(i as usize))
+                    .cloned();
+            }
+
+            // 最后的保底：阿拉伯数字
+            let final_icon = icon.unwrap_or_else(|| (i + 1).to_string());
+
+            let focused = (focused_tags & mask) != 0;
+            let is_occupied = (occupied & mask) != 0;
+            let count = self
+                .windows
+                .iter()
+                .filter(|w| {
+                    w.app_id.is_some()
+                        && !w.is_minimized
+                        && (w.tags & mask) != 0
+                        && output.is_none_or(|name| w.output.as_deref() == Some(name))
+                })
+                .count();
+
+            // --- 优先使用 [status] 模板，否则回退到 [waybar] 的样式前缀 ---
+            let template = status_cfg.and_then(|c| {
+                let specific = if focused {
+                    c.focused_tag.as_ref()
+                } else if is_occupied {
+                    c.occupied_tag.as_ref()
+                } else {
+                    c.empty_tag.as_ref()
+                };
+                specific.or(c.tag.as_ref())
+            });
+
+            let text = match template {
+                Some(t) => fill_template(
+                    t,
+                    &[
+                        ("index", &(i + 1).to_string()),
+                        ("icon", &final_icon),
+                        ("count", &count.to_string()),
+                        ("title", &markup_title),
+                        ("app_id", &markup_app_id),
+                        ("mode", mode),
+                    ],
+                ),
+                None => {
+                    let style_prefix = if focused {
+                        waybar_cfg.and_then(|c| c.focused_style.as_ref())
+                    } else if is_occupied {
+                        waybar_cfg.and_then(|c| c.occupied_style.as_ref())
+                    } else {
+                        waybar_cfg.and_then(|c| c.empty_style.as_ref())
+                    };
+                    match style_prefix {
+                        Some(prefix) => format!("{}{}</span>", prefix, final_icon),
+                        None => final_icon.clone(),
+                    }
+                }
+            };
+
+            tags.push(TagStatus {
+                index: i + 1,
+                icon: final_icon,
+                focused,
+                occupied: is_occupied,
+                count,
+                text,
+            });
+        }
+
+        // 3. 拼接整行文本与提示
+        let separator = status_cfg
+            .and_then(|c| c.separator.as_deref())
+            .unwrap_or("  ");
+        let tag_text = tags
+            .iter()
+            .map(|t| t.text.as_str())
+            .collect::<Vec<_>>()
+            .join(separator);
+        let line_vars = [
+            ("tags", tag_text.as_str()),
+            ("title", markup_title.as_str()),
+            ("app_id", markup_app_id.as_str()),
+            ("mode", mode),
+            ("layout", layout.as_str()),
+        ];
+        let text = match status_cfg.and_then(|c| c.format.as_ref()) {
+            Some(t) => fill_template(t, &line_vars),
+            None => tag_text.clone(),
+        };
+        let tooltip = match status_cfg.and_then(|c| c.tooltip.as_ref()) {
+            Some(t) => fill_template(t, &line_vars),
+            // 默认的 BSP 树不额外提示，其余布局附带当前显示的是第几个窗口
+            None if layout == "tree" => format!("Focus: {}", markup_app_id),
+            None => format!("Focus: {} [{}]", markup_app_id, layout),
+        };
+
+        StatusSnapshot {
+            output: output.map(|s| s.to_string()),
+            tags,
+            app_id,
+            title,
+            mode: mode.to_string(),
+            layout,
+            text,
+            tooltip,
+        }
+    }
+
+    /// 布局指示：monocle、scroller 与 tabbed/stacked 容器附带当前显示的是第几个窗口
+    fn layout_indicator(
+        &self,
+        out_name: Option<&str>,
+        tags: u32,
+        current: Option<&WindowData>,
+    ) -> String {
+        let key = match out_name {
+            Some(name) => (name.to_string(), tags),
+            None => return String::new(),
+        };
+        let root = self.layout_roots.get(&key);
+        let position = |ids: &[ObjectId]| {
+            current
+                .and_then(|w| ids.iter().position(|id| *id == w.id))
+                .map(|i| format!(" {}/{}", i + 1, ids.len()))
+                .unwrap_or_default()
+        };
+
+        match self.tag_layout(&key).mode {
+            LayoutMode::MasterStack => "master_stack".to_string(),
+            LayoutMode::Monocle => {
+                let ids: Vec<ObjectId> = root
+                    .map(|r| r.leaves().iter().map(|w| w.id.clone()).collect())
+                    .unwrap_or_default();
+                format!("monocle{}", position(&ids))
+            }
+            LayoutMode::Scroller => {
+                let ids: Vec<ObjectId> = root
+                    .map(|r| r.leaves().iter().map(|w| w.id.clone()).collect())
+                    .unwrap_or_default();
+                format!("scroller{}", position(&ids))
+            }
+            LayoutMode::Tree => match root.zip(current).and_then(|(r, w)| r.tab_group(&w.id)) {
+                Some((ContainerMode::Stacked, ids)) => format!("stacked{}", position(&ids)),
+                Some((_, ids)) => format!("tabbed{}", position(&ids)),
+                None => "tree".to_string(),
+            },
+        }
+    }
+
+    /// 获取某个显示器上“当前”的窗口：焦点显示器取焦点窗口，其余取当前标签的焦点记忆
+    fn get_output_window(&self, output: Option<&str>) -> Option<&WindowData> {
+        let name = match output {
+            Some(name) if self.focused_output.as_deref() != Some(name) => name,
+            _ => {
+                let f_id = self.focused_window.as_ref()?;
+                return self.windows.iter().find(|w| &w.id == f_id);
+            }
+        };
+        let tags = self.outputs.get(name).map(|o| o.tags).unwrap_or(0);
+        self.tag_focus_history
+            .get(&(name.to_string(), tags))
+            .and_then(|id| self.windows.iter().find(|w| &w.id == id))
+    }
+}
@@ -0,0 +1,162 @@
+use crate::wm::layout::LayoutNode;
+use crate::wm::AppState;
+use std::fs;
+use tracing::info;
+use wayland_backend::client::ObjectId;
+
+/// 沿父进程往上最多追溯的层数 (终端 -> shell -> 程序，一般两三层就到了)
+const MAX_DEPTH: usize = 32;
+
+/// 从 /proc/<pid>/stat 读出父进程号；进程名里可能有空格和括号，所以从最后一个 ')' 之后解析
+fn parent_pid(pid: i32) -> Option<i32> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let rest = &stat[stat.rfind(')')? + 1..];
+    rest.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// 由近到远的祖先进程 (不含 init)
+fn ancestors(pid: i32) -> Vec<i32> {
+    let mut chain = Vec::new();
+    let mut current = pid;
+    while chain.len() < MAX_DEPTH {
+        match parent_pid(current) {
+            Some(ppid) if ppid > 1 => {
+                chain.push(ppid);
+                current = ppid;
+            }
+            _ => break,
+        }
+    }
+    chain
+}
+
+impl AppState {
+    /// 新的平铺窗口如果是某个平铺终端的后代进程，就顶替终端在树里的位置并把终端藏起来
+    pub fn swallow_parent(&mut self, win_id: &ObjectId) -> bool {
+        if self.swallowed.contains_key(win_id) {
+            return true;
+        }
+        let (pid, own_key) = match self.windows.iter().find(|w| &w.id == win_id) {
+            Some(w) if !w.is_floating && !w.is_minimized && !w.is_terminal => {
+                match (w.pid, &w.output) {
+                    (Some(pid), Some(out)) => (pid, (out.clone(), w.tags)),
+                    _ => return false,
+                }
+            }
+            _ => return false,
+        };
+
+        // 离得最近的、还在树里的终端
+        let terminal = ancestors(pid).into_iter().find_map(|ppid| {
+            self.windows
+                .iter()
+                .filter(|w| w.pid == Some(ppid) && w.is_terminal)
+                .filter(|w| !w.is_floating && !w.is_minimized)
+                .find_map(|w| {
+                    let key = (w.output.clone()?, w.tags);
+                    let root = self.layout_roots.get(&key)?;
+                    root.split_path(&w.id).map(|_| (w.id.clone(), key))
+                })
+        });
+        let (term_id, term_key) = match terminal {
+            Some(t) => t,
+            None => return false,
+        };
+
+        // 先把新窗口从它自己的树里拿出来，再搬到终端所在的显示器和标签
+        if let Some(root) = self.layout_roots.remove(&own_key) {
+            if let Some(new_root) = LayoutNode::remove_at(root, win_id) {
+                self.layout_roots.insert(own_key, new_root);
+            }
+        }
+        let w_data = match self.windows.iter_mut().find(|w| &w.id == win_id) {
+            Some(w) => {
+                w.output = Some(term_key.0.clone());
+                w.tags = term_key.1;
+                w.last_proposed_w = 0;
+                w.last_proposed_h = 0;
+                w.clone()
+            }
+            None => return false,
+        };
+        if let Some(root) = self.layout_roots.get_mut(&term_key) {
+            root.replace_window(&term_id, w_data);
+        }
+        if let Some(t) = self.windows.iter_mut().find(|w| w.id == term_id) {
+            t.is_minimized = true;
+        }
+        info!("-> [Swallow] {:?} takes the place of {:?}", win_id, term_id);
+
+        self.swallowed.insert(win_id.clone(), term_id.clone());
+        self.tag_focus_history
+            .retain(|_, id| id != win_id && *id != term_id);
+        self.tag_focus_history
+            .insert(term_key.clone(), win_id.clone());
+        self.focused_window = Some(win_id.clone());
+        self.focused_output = Some(term_key.0);
+        true
+    }
+
+    /// 吞掉终端的窗口关闭了：终端回到这个窗口在树里的位置
+    pub fn release_swallowed(&mut self, win_id: &ObjectId) {
+        let term_id = match self.swallowed.remove(win_id) {
+            Some(t) => t,
+            None => return,
+        };
+        let key = match self.windows.iter().find(|w| &w.id == win_id) {
+            Some(w) => match &w.output {
+                Some(out) => (out.clone(), w.tags),
+                None => return,
+            },
+            None => return,
+        };
+        info!(
+            "-> [Swallow] {:?} closed, bringing back {:?}",
+            win_id, term_id
+        );
+        self.unhide_terminal(&term_id, key, Some(win_id));
+    }
+
+    /// 让藏起来的终端重新出现在 key 这棵树里：顶替 slot 里的窗口，
+    /// slot 已经不在树里 (被设成悬浮、最小化等) 时插到该标签的焦点窗口旁边
+    pub fn unhide_terminal(
+        &mut self,
+        term_id: &ObjectId,
+        key: (String, u32),
+        slot: Option<&ObjectId>,
+    ) {
+        let term_data = match self.windows.iter_mut().find(|w| &w.id == term_id) {
+            Some(t) => {
+                t.is_minimized = false;
+                t.output = Some(key.0.clone());
+                t.tags = key.1;
+                t.last_proposed_w = 0;
+                t.last_proposed_h = 0;
+                t.clone()
+            }
+            None => return,
+        };
+
+        let replaced = match (slot, self.layout_roots.get_mut(&key)) {
+            (Some(slot_id), Some(root)) => root.replace_window(slot_id, term_data.clone()),
+            _ => false,
+        };
+        if !replaced {
+            let target = self
+                .tag_focus_history
+                .get(&key)
+                .filter(|id| Some(*id) != slot)
+                .cloned()
+                .unwrap_or_else(|| term_id.clone());
+            self.insert_tiled(&key, &target, term_data, None);
+        }
+
+        if slot.is_some_and(|id| self.focused_window.as_ref() == Some(id)) {
+            self.focused_window = Some(term_id.clone());
+        }
+        self.tag_focus_history.insert(key, term_id.clone());
+        if let Some(wm) = &self.river_wm {
+            wm.manage_dirty();
+        }
+    }
+}